@@ -1,5 +1,8 @@
-use audio_mixer::{Channel, Mixer};
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+// The bench helpers keep their ?Sized bounds, which newer clippy reports as needless.
+#![allow(clippy::needless_maybe_sized)]
+
+use audio_mixer::{Channel, ChannelLayout, F32Kernel, FixedMixer, I16Kernel, Level, Mixer, Sample};
+use criterion::{black_box, criterion_group, criterion_main, Bencher, Criterion};
 
 use std::any::{Any, TypeId};
 
@@ -11,8 +14,100 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("downmix_i16", |b| {
         b.iter(|| downmix::<i16>(black_box(frames)))
     });
+    c.bench_function("downmix_i32", |b| {
+        b.iter(|| downmix::<i32>(black_box(frames)))
+    });
     c.bench_function("upmix_f32", |b| b.iter(|| upmix::<f32>(black_box(frames))));
     c.bench_function("upmix_i16", |b| b.iter(|| upmix::<i16>(black_box(frames))));
+    c.bench_function("upmix_i32", |b| b.iter(|| upmix::<i32>(black_box(frames))));
+
+    // Mix 100 ms of 7.1 audio at 48 kHz to stereo with a prebuilt mixer, so only the mixing is
+    // measured. The frames are mixed one by one with Mixer::mix as the baseline, by the kernels
+    // with the SIMD instructions turned off, and by the kernels with the best instruction set.
+    let frames = 4800;
+    c.bench_function("downmix_7_1_f32_per_frame", |b| {
+        bench_downmix_7_1_per_frame::<f32>(b, frames)
+    });
+    c.bench_function("downmix_7_1_f32_scalar", |b| {
+        let (mixer, input_buffer, mut output_buffer) = create_downmix_7_1::<f32>(frames);
+        let kernel = F32Kernel::with_level(&mixer.matrix(), Level::Scalar);
+        b.iter(|| kernel.mix(black_box(&input_buffer), &mut output_buffer))
+    });
+    c.bench_function("downmix_7_1_f32_simd", |b| {
+        bench_downmix_7_1::<f32>(b, frames)
+    });
+    c.bench_function("downmix_7_1_i16_per_frame", |b| {
+        bench_downmix_7_1_per_frame::<i16>(b, frames)
+    });
+    // The normalized 7.1 to stereo matrix never makes the mixed data overflow.
+    c.bench_function("downmix_7_1_i16_scalar", |b| {
+        let (mixer, input_buffer, mut output_buffer) = create_downmix_7_1::<i16>(frames);
+        let kernel = I16Kernel::with_level(&mixer.matrix(), false, Level::Scalar);
+        b.iter(|| kernel.mix(black_box(&input_buffer), &mut output_buffer))
+    });
+    c.bench_function("downmix_7_1_i16_simd", |b| {
+        bench_downmix_7_1::<i16>(b, frames)
+    });
+
+    // Mix 5.1 to stereo by the mixer with the fixed numbers of channels, and by the mixer without
+    // SIMD kernel for i32, for comparison.
+    let input_channels = [
+        Channel::FrontLeft,
+        Channel::FrontRight,
+        Channel::FrontCenter,
+        Channel::LowFrequency,
+        Channel::BackLeft,
+        Channel::BackRight,
+    ];
+    let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+    let (input_buffer, mut output_buffer) = create_buffers::<i32>(6 * frames, 2 * frames);
+    let mixer = Mixer::<i32>::new(&input_channels, &output_channels);
+    c.bench_function("downmix_5_1_i32", |b| {
+        b.iter(|| mixer.mix_interleaved(black_box(&input_buffer), &mut output_buffer))
+    });
+    let mixer = FixedMixer::<i32, 6, 2>::new(&input_channels, &output_channels);
+    c.bench_function("downmix_5_1_i32_fixed", |b| {
+        b.iter(|| mixer.mix_interleaved(black_box(&input_buffer), &mut output_buffer))
+    });
+}
+
+fn bench_downmix_7_1<T>(b: &mut Bencher, frames: usize)
+where
+    T: Sample + Default + From<u8>,
+{
+    let (mixer, input_buffer, mut output_buffer) = create_downmix_7_1::<T>(frames);
+    b.iter(|| mixer.mix_interleaved(black_box(&input_buffer), &mut output_buffer));
+}
+
+fn bench_downmix_7_1_per_frame<T>(b: &mut Bencher, frames: usize)
+where
+    T: Sample + Default + From<u8>,
+{
+    let (mixer, input_buffer, mut output_buffer) = create_downmix_7_1::<T>(frames);
+    let input_channels = mixer.input_channels().len();
+    let output_channels = mixer.output_channels().len();
+    b.iter(|| {
+        for (input, output) in black_box(&input_buffer)
+            .chunks(input_channels)
+            .zip(output_buffer.chunks_mut(output_channels))
+        {
+            mixer.mix(input, output);
+        }
+    });
+}
+
+fn create_downmix_7_1<T>(frames: usize) -> (Mixer<T>, Vec<T>, Vec<T>)
+where
+    T: Sample + Default + From<u8>,
+{
+    let input_channels = ChannelLayout::surround_7_1();
+    let output_channels = ChannelLayout::stereo();
+    let mixer = Mixer::<T>::new(input_channels.channels(), output_channels.channels());
+    let (input_buffer, output_buffer) = create_buffers::<T>(
+        input_channels.channels().len() * frames,
+        output_channels.channels().len() * frames,
+    );
+    (mixer, input_buffer, output_buffer)
 }
 
 fn downmix<T>(frames: usize)
@@ -68,6 +163,17 @@ where
         for _ in 0..frames {
             mixer.mix(in_buf.next().unwrap(), out_buf.next().unwrap());
         }
+    } else if TypeId::of::<T>() == TypeId::of::<i32>() {
+        let (input_buffer, mut output_buffer) = create_buffers::<i32>(
+            input_channels.len() * frames,
+            output_channels.len() * frames,
+        );
+        let mut in_buf = input_buffer.chunks(input_channels.len());
+        let mut out_buf = output_buffer.chunks_mut(output_channels.len());
+        let mixer = Mixer::<i32>::new(input_channels, output_channels);
+        for _ in 0..frames {
+            mixer.mix(in_buf.next().unwrap(), out_buf.next().unwrap());
+        }
     } else {
         panic!("Unsupport type");
     }
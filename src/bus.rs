@@ -0,0 +1,461 @@
+use crate::channel::Channel;
+use crate::coefficient::{Coefficient, MixerError, Side};
+use crate::config::MixerConfig;
+use crate::sample::Sample;
+use crate::stream::State;
+use crate::{interleaved_frames, Mixer};
+
+// The identifier of a source registered in a MixBus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SourceId(usize);
+
+#[derive(Debug)]
+struct Source<I, O>
+where
+    I: Sample,
+    O: Sample,
+{
+    id: SourceId,
+    mixer: Mixer<I, O>,
+    gain: f64,
+    muted: bool,
+}
+
+// A bus mixing several sources, each with its own channel layout, into one output layout.
+// Every source has its own Mixer whose matrix is generated from the source layout and the
+// output layout, with the same config. The sources are mixed with their gains and added up in
+// f64, and the sums are converted to the output samples only once, so the result doesn't depend
+// on the order of the sources. The dither, noise shaping and output protection in the config are
+// applied to the sums, so the bus must be used for only one continuous output stream.
+#[derive(Debug)]
+pub struct MixBus<I, O = I>
+where
+    I: Sample,
+    O: Sample,
+{
+    config: MixerConfig,
+    sources: Vec<Source<I, O>>,
+    next_id: usize,
+    // The mixer copying the sums to the output channels, which converts them to the output
+    // samples, and its state for the dither or output protection.
+    output: Mixer<f64, O>,
+    state: Option<State>,
+    // The sums of the sources in the output channels, reused by every render.
+    sums: Vec<f64>,
+}
+
+impl<I, O> MixBus<I, O>
+where
+    I: Sample,
+    O: Sample,
+{
+    // Panic if the output channels are invalid. Use with_config to handle the error instead.
+    pub fn new(output_channels: &[Channel]) -> Self {
+        Self::with_config(output_channels, &MixerConfig::default()).expect("Invalid channel layout")
+    }
+
+    // Create a bus whose sources are mixed with the levels in the given config. Return an error
+    // if there are duplicate non-silence channels in output channels, or the output protection
+    // is invalid.
+    pub fn with_config(
+        output_channels: &[Channel],
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let identity: Vec<Vec<f64>> = (0..output_channels.len())
+            .map(|i| {
+                (0..output_channels.len())
+                    .map(|j| if i == j { 1.0 } else { 0.0 })
+                    .collect()
+            })
+            .collect();
+        let coefficient =
+            Coefficient::create_with_matrix(output_channels, output_channels, &identity)?;
+        let state = State::new(
+            config,
+            output_channels.len(),
+            coefficient.would_overflow_from_coefficient_value(),
+        )?;
+        Ok(Self {
+            config: config.clone(),
+            sources: Vec::new(),
+            next_id: 0,
+            output: Mixer::with_coefficient(coefficient),
+            state,
+            sums: Vec::new(),
+        })
+    }
+
+    // Register a source with the given channel layout, with gain 1.0 and not muted. Return an
+    // error if there are duplicate non-silence channels in the source or output channels.
+    pub fn add_source(&mut self, input_channels: &[Channel]) -> Result<SourceId, MixerError> {
+        let coefficient =
+            Coefficient::create(input_channels, self.output_channels(), &self.config)?;
+        let mixer = Mixer::with_coefficient(coefficient);
+        let id = SourceId(self.next_id);
+        self.next_id += 1;
+        self.sources.push(Source {
+            id,
+            mixer,
+            gain: 1.0,
+            muted: false,
+        });
+        Ok(id)
+    }
+
+    // Return false if the source is not in the bus.
+    pub fn remove_source(&mut self, id: SourceId) -> bool {
+        let count = self.sources.len();
+        self.sources.retain(|source| source.id != id);
+        self.sources.len() != count
+    }
+
+    // Set the linear gain applied to the mixed data of the source. Return false if the source
+    // is not in the bus.
+    pub fn set_gain(&mut self, id: SourceId, gain: f64) -> bool {
+        assert!(gain.is_finite(), "gain must be a finite number.");
+        self.source_mut(id)
+            .map(|source| source.gain = gain)
+            .is_some()
+    }
+
+    // A muted source is skipped when rendering, no matter what its gain is. Return false if the
+    // source is not in the bus.
+    pub fn set_muted(&mut self, id: SourceId, muted: bool) -> bool {
+        self.source_mut(id)
+            .map(|source| source.muted = muted)
+            .is_some()
+    }
+
+    pub fn gain(&self, id: SourceId) -> Option<f64> {
+        self.source(id).map(|source| source.gain)
+    }
+
+    pub fn is_muted(&self, id: SourceId) -> Option<bool> {
+        self.source(id).map(|source| source.muted)
+    }
+
+    // Return the mixer used for the source, which has the mixing matrix of the source.
+    pub fn mixer(&self, id: SourceId) -> Option<&Mixer<I, O>> {
+        self.source(id).map(|source| &source.mixer)
+    }
+
+    // Return the sources in the order they are added.
+    pub fn sources(&self) -> Vec<SourceId> {
+        self.sources.iter().map(|source| source.id).collect()
+    }
+
+    pub fn output_channels(&self) -> &[Channel] {
+        self.output.output_channels()
+    }
+
+    // Return the number of frames the output data is delayed by. See MixerStream::latency.
+    pub fn latency(&self) -> usize {
+        match &self.state {
+            Some(State::Protection(protector)) => protector.latency(),
+            _ => 0,
+        }
+    }
+
+    // Mix the interleaved input buffers of the sources into the interleaved output buffer,
+    // which is overwritten, and return the number of mixed frames. Each input buffer is paired
+    // with the source it belongs to, and must contain the same number of frames as the output
+    // buffer, even if the source is muted. The sources without input buffers are silent. Return
+    // an error without touching the output buffer if any of the sources is not in the bus, is
+    // given more than one input buffer, or its input buffer has a different number of frames.
+    pub fn render(
+        &mut self,
+        inputs: &[(SourceId, &[I])],
+        output_buffer: &mut [O],
+    ) -> Result<usize, MixerError> {
+        let frames = interleaved_frames(output_buffer, self.output_channels().len(), Side::Output);
+        for (index, (id, input_buffer)) in inputs.iter().enumerate() {
+            let source = self
+                .source(*id)
+                .ok_or(MixerError::UnknownSource { id: *id })?;
+            if inputs[..index].iter().any(|(other, _)| other == id) {
+                return Err(MixerError::DuplicateSource { id: *id });
+            }
+            let expected = frames * source.mixer.input_channels().len();
+            if input_buffer.len() != expected {
+                return Err(MixerError::SourceBufferSizeMismatch {
+                    id: *id,
+                    expected,
+                    actual: input_buffer.len(),
+                });
+            }
+        }
+        self.sums.clear();
+        self.sums.resize(output_buffer.len(), 0.0);
+        for (id, input_buffer) in inputs {
+            let source = self
+                .sources
+                .iter()
+                .find(|source| source.id == *id)
+                .expect("source must be in the bus.");
+            if !source.muted {
+                source
+                    .mixer
+                    .mix_add_f64(input_buffer, &mut self.sums, source.gain);
+            }
+        }
+        Ok(self
+            .output
+            .mix_interleaved_with_state(self.state.as_mut(), &self.sums, output_buffer))
+    }
+
+    fn source(&self, id: SourceId) -> Option<&Source<I, O>> {
+        self.sources.iter().find(|source| source.id == id)
+    }
+
+    fn source_mut(&mut self, id: SourceId) -> Option<&mut Source<I, O>> {
+        self.sources.iter_mut().find(|source| source.id == id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::ChannelLayout;
+
+    #[test]
+    fn test_sources() {
+        let mut bus = MixBus::<f32>::new(&ChannelLayout::stereo());
+        let media = bus.add_source(&ChannelLayout::surround_5_1()).unwrap();
+        let voice = bus.add_source(&ChannelLayout::mono()).unwrap();
+        assert_ne!(media, voice);
+        assert_eq!(bus.sources(), vec![media, voice]);
+        assert_eq!(bus.gain(media), Some(1.0));
+        assert_eq!(bus.is_muted(voice), Some(false));
+        assert_eq!(
+            bus.mixer(media).unwrap().matrix(),
+            Mixer::<f32>::new(&ChannelLayout::surround_5_1(), &ChannelLayout::stereo()).matrix()
+        );
+
+        assert!(bus.set_gain(media, 0.5));
+        assert!(bus.set_muted(voice, true));
+        assert_eq!(bus.gain(media), Some(0.5));
+        assert_eq!(bus.is_muted(voice), Some(true));
+
+        assert!(bus.remove_source(media));
+        assert!(!bus.remove_source(media));
+        assert!(!bus.set_gain(media, 1.0));
+        assert_eq!(bus.gain(media), None);
+        assert_eq!(bus.sources(), vec![voice]);
+
+        // The removed ids are never reused.
+        let notification = bus.add_source(&ChannelLayout::stereo()).unwrap();
+        assert_ne!(notification, media);
+
+        let duplicate = [Channel::FrontLeft, Channel::FrontLeft];
+        assert_eq!(
+            bus.add_source(&duplicate),
+            Err(MixerError::DuplicateNonSilenceChannel {
+                side: crate::Side::Input,
+                channel: Channel::FrontLeft,
+            })
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let output_channels = ChannelLayout::stereo();
+        let mut bus = MixBus::<i16>::new(&output_channels);
+        let media = bus.add_source(&ChannelLayout::surround_5_1()).unwrap();
+        let voice = bus.add_source(&ChannelLayout::mono()).unwrap();
+
+        let frames = 8;
+        let media_input: Vec<i16> = (0..frames * 6).map(|i| (i * 331) as i16).collect();
+        let voice_input: Vec<i16> = (0..frames)
+            .map(|i| (i as i16).wrapping_mul(-1009))
+            .collect();
+        let mut media_output = vec![0; frames * 2];
+        bus.mixer(media)
+            .unwrap()
+            .mix_interleaved(&media_input, &mut media_output);
+        let mut voice_output = vec![0; frames * 2];
+        bus.mixer(voice)
+            .unwrap()
+            .mix_interleaved(&voice_input, &mut voice_output);
+
+        // The sums are mixed in f64 and rounded only once, so they are within 1 LSB of the data
+        // mixed by the i16 mixers.
+        let assert_close = |output: &[i16], expected: &[i16]| {
+            for (data, expected) in output.iter().zip(expected) {
+                assert!((i32::from(*data) - i32::from(*expected)).abs() <= 1);
+            }
+        };
+
+        // The output is overwritten by the sum of the sources.
+        let mut output = vec![i16::MAX; frames * 2];
+        let inputs: [(SourceId, &[i16]); 2] = [(media, &media_input), (voice, &voice_input)];
+        assert_eq!(bus.render(&inputs, &mut output), Ok(frames));
+        let sums: Vec<i16> = media_output
+            .iter()
+            .zip(&voice_output)
+            .map(|(media, voice)| media + voice)
+            .collect();
+        assert_close(&output, &sums);
+
+        // The muted source and the source without input are silent.
+        bus.set_muted(voice, true);
+        bus.render(&inputs, &mut output).unwrap();
+        assert_close(&output, &media_output);
+        bus.set_muted(voice, false);
+        bus.render(&inputs[1..], &mut output).unwrap();
+        assert_close(&output, &voice_output);
+
+        bus.set_gain(voice, 0.5);
+        bus.render(&inputs[1..], &mut output).unwrap();
+        for (data, voice) in output.iter().zip(&voice_output) {
+            assert!((i32::from(*data) - i32::from(*voice) / 2).abs() <= 1);
+        }
+
+        // The removed source is rejected, and the output is untouched.
+        assert!(bus.remove_source(media));
+        let mut output = vec![1; frames * 2];
+        assert_eq!(
+            bus.render(&inputs, &mut output),
+            Err(MixerError::UnknownSource { id: media })
+        );
+        assert_eq!(output, vec![1; frames * 2]);
+
+        // The silence of u8 is 128.
+        let mut bus = MixBus::<u8>::new(&output_channels);
+        let _ = bus.add_source(&ChannelLayout::mono()).unwrap();
+        let mut output = vec![0; 4];
+        assert_eq!(bus.render(&[], &mut output), Ok(2));
+        assert_eq!(output, vec![128; 4]);
+    }
+
+    #[test]
+    fn test_render_in_any_order() {
+        // The loud sources cancel each other out, so the result is in range, and it's the same
+        // no matter which source is added first.
+        let channels = [Channel::FrontCenter];
+        let mut bus = MixBus::<i16>::new(&channels);
+        let first = bus.add_source(&channels).unwrap();
+        let second = bus.add_source(&channels).unwrap();
+        let third = bus.add_source(&channels).unwrap();
+        let loud: &[i16] = &[30000];
+        let negative: &[i16] = &[-30000];
+        let mut output = [0; 1];
+        bus.render(
+            &[(first, loud), (second, loud), (third, negative)],
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, [30000]);
+        bus.render(
+            &[(first, loud), (third, negative), (second, loud)],
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, [30000]);
+
+        // Only the final sum is clipped.
+        bus.render(&[(first, loud), (second, loud)], &mut output)
+            .unwrap();
+        assert_eq!(output, [i16::MAX]);
+    }
+
+    #[test]
+    fn test_render_with_config() {
+        use crate::dither::Dither;
+        use crate::protection::OutputProtection;
+
+        // The output protection is applied to the sum of the sources.
+        let channels = [Channel::FrontCenter];
+        let config = MixerConfig::default().with_output_protection(OutputProtection::HardClip);
+        let mut bus = MixBus::<f32>::with_config(&channels, &config).unwrap();
+        assert_eq!(bus.latency(), 0);
+        let first = bus.add_source(&channels).unwrap();
+        let second = bus.add_source(&channels).unwrap();
+        let input: &[f32] = &[0.75, -0.25];
+        let mut output = [0.0; 2];
+        bus.render(&[(first, input), (second, input)], &mut output)
+            .unwrap();
+        assert_eq!(output, [1.0, -0.5]);
+
+        // The dither is applied to the sum of the sources, so the average output level matches
+        // the sum of the sub-LSB sources, which is always rounded to 0 without dither.
+        let frames = 4096;
+        let input = vec![1_i16; frames];
+        let render = |config: &MixerConfig| {
+            let mut bus = MixBus::<i16>::with_config(&channels, config).unwrap();
+            let first = bus.add_source(&channels).unwrap();
+            let second = bus.add_source(&channels).unwrap();
+            bus.set_gain(first, 0.125);
+            bus.set_gain(second, 0.125);
+            let mut output = vec![0; frames];
+            bus.render(&[(first, &input), (second, &input)], &mut output)
+                .unwrap();
+            output
+        };
+        assert!(render(&MixerConfig::default())
+            .iter()
+            .all(|data| *data == 0));
+        let output = render(&MixerConfig::default().with_dither(Dither::Triangular));
+        let mean = output.iter().map(|data| f64::from(*data)).sum::<f64>() / frames as f64;
+        assert!((mean - 0.25).abs() < 0.05);
+
+        // The invalid output protection is rejected.
+        let config = MixerConfig::default().with_output_protection(OutputProtection::Limiter {
+            threshold: 0.0,
+            lookahead: 32,
+            release: 100,
+        });
+        assert_eq!(
+            MixBus::<f32>::with_config(&channels, &config).unwrap_err(),
+            MixerError::InvalidLimiterThreshold { threshold: 0.0 }
+        );
+    }
+
+    #[test]
+    fn test_render_with_different_frames() {
+        let mut bus = MixBus::<f32>::new(&ChannelLayout::stereo());
+        let voice = bus.add_source(&ChannelLayout::mono()).unwrap();
+        let media = bus.add_source(&ChannelLayout::stereo()).unwrap();
+        let mut output = vec![1.0; 8];
+
+        // The input buffer must have the same number of frames as the output buffer, even if
+        // the source is muted.
+        let short: &[f32] = &[0.0; 3];
+        let expected = Err(MixerError::SourceBufferSizeMismatch {
+            id: voice,
+            expected: 4,
+            actual: 3,
+        });
+        assert_eq!(bus.render(&[(voice, short)], &mut output), expected);
+        bus.set_muted(voice, true);
+        assert_eq!(bus.render(&[(voice, short)], &mut output), expected);
+        let incomplete: &[f32] = &[0.0; 7];
+        assert_eq!(
+            bus.render(&[(media, incomplete)], &mut output),
+            Err(MixerError::SourceBufferSizeMismatch {
+                id: media,
+                expected: 8,
+                actual: 7,
+            })
+        );
+        assert_eq!(output, vec![1.0; 8]);
+    }
+
+    #[test]
+    fn test_render_with_duplicate_sources() {
+        let mut bus = MixBus::<f32>::new(&ChannelLayout::stereo());
+        let voice = bus.add_source(&ChannelLayout::mono()).unwrap();
+        let media = bus.add_source(&ChannelLayout::stereo()).unwrap();
+        let mut output = vec![1.0; 8];
+
+        // Every source can be given only one input buffer.
+        let input: &[f32] = &[0.5; 8];
+        assert_eq!(
+            bus.render(
+                &[(media, input), (voice, &input[..4]), (media, input)],
+                &mut output
+            ),
+            Err(MixerError::DuplicateSource { id: media })
+        );
+        assert_eq!(output, vec![1.0; 8]);
+    }
+}
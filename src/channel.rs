@@ -1,3 +1,5 @@
+use std::ops::Deref;
+
 // The number of channels must be unique and start from 0. They will be treated as indice in the
 // mixing matrix and used to form unique bitflags in the channel map, which is a bitmap.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -69,6 +71,8 @@ impl ChannelMap {
     pub const FRONT_2_OF_CENTER: Self =
         Self::union(Self::FRONT_LEFT_OF_CENTER, Self::FRONT_RIGHT_OF_CENTER);
     pub const SIDE_2: Self = Self::union(Self::SIDE_LEFT, Self::SIDE_RIGHT);
+    pub const TOP_FRONT_2: Self = Self::union(Self::TOP_FRONT_LEFT, Self::TOP_FRONT_RIGHT);
+    pub const TOP_BACK_2: Self = Self::union(Self::TOP_BACK_LEFT, Self::TOP_BACK_RIGHT);
 }
 
 impl From<Channel> for ChannelMap {
@@ -76,3 +80,205 @@ impl From<Channel> for ChannelMap {
         ChannelMap::from_bits(channel.bitmask()).expect("convert an invalid channel")
     }
 }
+
+// The channel layout of the audio data. The channels are listed in the order they are stored
+// in the audio data. ChannelLayout can be passed to wherever a &[Channel] is expected, e.g.,
+// Mixer::new(&ChannelLayout::surround_5_1(), &ChannelLayout::stereo()).
+//
+// The preset layouts are listed in the order used by SMPTE, WAVE (WAVEFORMATEXTENSIBLE), cubeb
+// and ffmpeg. All of them order the channels by their channel-mask bits, which is the order
+// defined in enum Channel, so the only difference among them is which channels a layout picks.
+// The layout names used by WAVE (KSAUDIO_SPEAKER_*), cubeb (CUBEB_LAYOUT_*) and ffmpeg
+// (AV_CH_LAYOUT_*) are noted for each preset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelLayout {
+    channels: Vec<Channel>,
+    channel_map: ChannelMap,
+}
+
+impl ChannelLayout {
+    // The channels are not validated here. The duplicate channels are reported when the layout
+    // is used to create a mixer.
+    pub fn new(channels: &[Channel]) -> Self {
+        let mut channel_map = ChannelMap::empty();
+        for channel in channels {
+            channel_map.insert(ChannelMap::from(*channel));
+        }
+        Self {
+            channels: channels.to_vec(),
+            channel_map,
+        }
+    }
+
+    // FC. KSAUDIO_SPEAKER_MONO, CUBEB_LAYOUT_MONO, AV_CH_LAYOUT_MONO.
+    pub fn mono() -> Self {
+        Self::new(&[Channel::FrontCenter])
+    }
+
+    // FL, FR. KSAUDIO_SPEAKER_STEREO, CUBEB_LAYOUT_STEREO, AV_CH_LAYOUT_STEREO.
+    pub fn stereo() -> Self {
+        Self::new(&[Channel::FrontLeft, Channel::FrontRight])
+    }
+
+    // FL, FR, LFE. CUBEB_LAYOUT_STEREO_LFE, AV_CH_LAYOUT_2POINT1.
+    pub fn surround_2_1() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::LowFrequency,
+        ])
+    }
+
+    // FL, FR, FC. CUBEB_LAYOUT_3F, AV_CH_LAYOUT_SURROUND.
+    pub fn surround_3_0() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+        ])
+    }
+
+    // FL, FR, BL, BR. KSAUDIO_SPEAKER_QUAD, AV_CH_LAYOUT_QUAD.
+    pub fn quad() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::BackLeft,
+            Channel::BackRight,
+        ])
+    }
+
+    // FL, FR, SL, SR. CUBEB_LAYOUT_2F2, AV_CH_LAYOUT_2_2.
+    pub fn quad_side() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ])
+    }
+
+    // FL, FR, FC, SL, SR. CUBEB_LAYOUT_3F2, AV_CH_LAYOUT_5POINT0.
+    pub fn surround_5_0() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ])
+    }
+
+    // FL, FR, FC, BL, BR. AV_CH_LAYOUT_5POINT0_BACK.
+    pub fn surround_5_0_back() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::BackLeft,
+            Channel::BackRight,
+        ])
+    }
+
+    // FL, FR, FC, LFE, SL, SR.
+    // KSAUDIO_SPEAKER_5POINT1_SURROUND, CUBEB_LAYOUT_3F2_LFE, AV_CH_LAYOUT_5POINT1.
+    pub fn surround_5_1() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ])
+    }
+
+    // FL, FR, FC, LFE, BL, BR.
+    // KSAUDIO_SPEAKER_5POINT1, CUBEB_LAYOUT_3F2_LFE_BACK, AV_CH_LAYOUT_5POINT1_BACK.
+    pub fn surround_5_1_back() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::BackLeft,
+            Channel::BackRight,
+        ])
+    }
+
+    // FL, FR, FC, LFE, BC, SL, SR. CUBEB_LAYOUT_3F3R_LFE, AV_CH_LAYOUT_6POINT1.
+    pub fn surround_6_1() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::BackCenter,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ])
+    }
+
+    // FL, FR, FC, LFE, BL, BR, SL, SR.
+    // KSAUDIO_SPEAKER_7POINT1_SURROUND, CUBEB_LAYOUT_3F4_LFE, AV_CH_LAYOUT_7POINT1.
+    pub fn surround_7_1() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::BackLeft,
+            Channel::BackRight,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ])
+    }
+
+    // FL, FR, FC, LFE, BL, BR, TFL, TFR. AV_CH_LAYOUT_5POINT1POINT2_BACK.
+    pub fn surround_5_1_2_back() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::BackLeft,
+            Channel::BackRight,
+            Channel::TopFrontLeft,
+            Channel::TopFrontRight,
+        ])
+    }
+
+    // FL, FR, FC, LFE, BL, BR, SL, SR, TFL, TFR, TBL, TBR. AV_CH_LAYOUT_7POINT1POINT4_BACK.
+    pub fn surround_7_1_4() -> Self {
+        Self::new(&[
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::BackLeft,
+            Channel::BackRight,
+            Channel::SideLeft,
+            Channel::SideRight,
+            Channel::TopFrontLeft,
+            Channel::TopFrontRight,
+            Channel::TopBackLeft,
+            Channel::TopBackRight,
+        ])
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    pub(crate) fn channel_map(&self) -> ChannelMap {
+        self.channel_map
+    }
+}
+
+impl Deref for ChannelLayout {
+    type Target = [Channel];
+
+    fn deref(&self) -> &[Channel] {
+        &self.channels
+    }
+}
@@ -1,63 +1,227 @@
 // The code is based from libcubeb's cubeb_mixer.cpp,
 // which adapts the code from libswresample's rematrix.c
 
-use crate::channel::{Channel, ChannelMap};
+use crate::bus::SourceId;
+use crate::channel::{Channel, ChannelLayout, ChannelMap};
+use crate::config::{MixerConfig, Normalization};
+use crate::sample::{I24In32, I24};
 
-use std::fmt::Debug;
+use std::error;
+use std::fmt::{self, Debug, Display};
+use std::ops::{AddAssign, Mul};
 
 const CHANNELS: usize = Channel::count();
 
-#[derive(Debug)]
-enum Error {
-    DuplicateNonSilenceChannel,
-    AsymmetricChannels,
+// The side of the mixer where a channel layout is used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Input,
+    Output,
 }
 
-#[derive(Debug)]
-struct ChannelLayout {
-    channels: Vec<Channel>,
-    channel_map: ChannelMap,
+impl Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Input => write!(f, "input"),
+            Side::Output => write!(f, "output"),
+        }
+    }
 }
 
-impl ChannelLayout {
-    fn new(channels: &[Channel]) -> Result<Self, Error> {
-        let channel_map = Self::get_channel_map(channels)?;
-        Ok(Self {
-            channels: channels.to_vec(),
-            channel_map,
-        })
-    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MixerError {
+    // The channel appears more than once in the layout. Only Silence can be duplicate.
+    DuplicateNonSilenceChannel {
+        side: Side,
+        channel: Channel,
+    },
+    // The number of rows (output side) or columns (input side) of the given mixing matrix is
+    // different from the number of channels on that side.
+    MatrixSizeMismatch {
+        side: Side,
+        expected: usize,
+        actual: usize,
+    },
+    // The coefficient in the given mixing matrix is NaN or infinite.
+    NonFiniteCoefficient {
+        row: usize,
+        column: usize,
+    },
+    // The threshold of the limiter in the output protection is not in (0.0, 1.0].
+    InvalidLimiterThreshold {
+        threshold: f64,
+    },
+    // The config enables the processing whose state depends on the previous samples, which
+    // needs a MixerStream owned by each stream rather than a Mixer.
+    StatefulConfig,
+    // The source is not in the MixBus, e.g., it has been removed.
+    UnknownSource {
+        id: SourceId,
+    },
+    // The source is given more than one input buffer to render.
+    DuplicateSource {
+        id: SourceId,
+    },
+    // The number of samples in the input buffer of the source is different from the number of
+    // samples in the frames the output buffer contains.
+    SourceBufferSizeMismatch {
+        id: SourceId,
+        expected: usize,
+        actual: usize,
+    },
+}
 
-    // Except Silence channel, the duplicate channels are not allowed.
-    fn get_channel_map(channels: &[Channel]) -> Result<ChannelMap, Error> {
-        let mut map = ChannelMap::empty();
-        for channel in channels {
-            let bitmask = ChannelMap::from(*channel);
-            if channel != &Channel::Silence && map.contains(bitmask) {
-                return Err(Error::DuplicateNonSilenceChannel);
+impl Display for MixerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MixerError::DuplicateNonSilenceChannel { side, channel } => write!(
+                f,
+                "duplicate non-silence channel {:?} in {} layout",
+                channel, side
+            ),
+            MixerError::MatrixSizeMismatch {
+                side,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "mixing matrix has {} {}s but {} layout has {} channels",
+                actual,
+                match side {
+                    Side::Input => "column",
+                    Side::Output => "row",
+                },
+                side,
+                expected
+            ),
+            MixerError::NonFiniteCoefficient { row, column } => write!(
+                f,
+                "coefficient at row {} column {} of mixing matrix is not finite",
+                row, column
+            ),
+            MixerError::InvalidLimiterThreshold { threshold } => {
+                write!(f, "limiter threshold {} is not in (0.0, 1.0]", threshold)
+            }
+            MixerError::StatefulConfig => {
+                write!(
+                    f,
+                    "config needs a MixerStream to keep the state of the stream"
+                )
+            }
+            MixerError::UnknownSource { id } => write!(f, "source {:?} is not in the bus", id),
+            MixerError::DuplicateSource { id } => {
+                write!(f, "source {:?} has more than one input buffer", id)
             }
-            map.insert(bitmask);
+            MixerError::SourceBufferSizeMismatch {
+                id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "input buffer of source {:?} has {} samples but {} are expected",
+                id, actual, expected
+            ),
         }
-        Ok(map)
     }
 }
 
+impl error::Error for MixerError {}
+
+// Except Silence channel, the duplicate channels are not allowed.
+fn check_duplicate_channels(layout: &ChannelLayout, side: Side) -> Result<(), MixerError> {
+    let mut map = ChannelMap::empty();
+    for channel in layout.channels() {
+        let bitmask = ChannelMap::from(*channel);
+        if channel != &Channel::Silence && map.contains(bitmask) {
+            return Err(MixerError::DuplicateNonSilenceChannel {
+                side,
+                channel: *channel,
+            });
+        }
+        map.insert(bitmask);
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct Coefficient<T>
 where
     T: MixingCoefficient,
-    T::Coef: Copy,
 {
     input_layout: ChannelLayout,
     output_layout: ChannelLayout,
-    matrix: Vec<Vec<T::Coef>>,
-    would_overflow_from_coefficient_value: Option<bool>, // Only used when T is i16
+    normalized_matrix: Vec<Vec<f64>>, // The matrix before being converted to T::Coef.
+    // The matrix converted to T::Coef in row-major order, where the coefficient for mixing
+    // input channel j into output channel i is matrix[i * M + j].
+    matrix: Vec<T::Coef>,
+    would_overflow_from_coefficient_value: Option<bool>, // Only used when T is an integer type
+    plan: Plan<T::Coef>,
+}
+
+// A non-zero coefficient of the matrix, for mixing input channel data into output channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Triplet<C> {
+    pub input: usize,
+    pub output: usize,
+    pub coefficient: C,
+}
+
+// How the data of an output channel is mixed.
+#[derive(Debug, PartialEq)]
+pub enum Row<'a, C> {
+    // All the coefficients are zero, so the output channel is silent.
+    Zero,
+    // The data of the input channel is copied as it is, since its coefficient is 1.0 and the
+    // others are zero.
+    Copy(usize),
+    // The sum of the products of the non-zero coefficients and the input data.
+    Sum(&'a [Triplet<C>]),
+}
+
+// The execution plan compiled from the matrix, so the mixing cost scales with the number of
+// non-zero coefficients. The triplets are sorted by the output channel, and the triplets of
+// output channel i are triplets[ranges[i].0..ranges[i].1].
+#[derive(Debug)]
+struct Plan<C> {
+    triplets: Vec<Triplet<C>>,
+    ranges: Vec<(usize, usize)>,
+    copies: Vec<Option<usize>>,
+}
+
+impl<C: Copy> Plan<C> {
+    // Compile the plan from the normalized matrix and the row-major matrix converted from it.
+    fn compile(normalized_matrix: &[Vec<f64>], matrix: &[C]) -> Self {
+        let mut triplets = Vec::new();
+        let mut ranges = Vec::with_capacity(normalized_matrix.len());
+        let mut copies = Vec::with_capacity(normalized_matrix.len());
+        for (output, coefs) in normalized_matrix.iter().enumerate() {
+            let start = triplets.len();
+            for (input, coef) in coefs.iter().enumerate() {
+                if *coef != 0.0 {
+                    triplets.push(Triplet {
+                        input,
+                        output,
+                        coefficient: matrix[output * coefs.len() + input],
+                    });
+                }
+            }
+            ranges.push((start, triplets.len()));
+            copies.push(match &triplets[start..] {
+                [triplet] if coefs[triplet.input] == 1.0 => Some(triplet.input),
+                _ => None,
+            });
+        }
+        Self {
+            triplets,
+            ranges,
+            copies,
+        }
+    }
 }
 
 impl<T> Coefficient<T>
 where
     T: MixingCoefficient,
-    T::Coef: Copy,
 {
     // Given a M-channel input layout and a N-channel output layout, generate a NxM coefficients
     // matrix m such that out_audio = m * in_audio, where in_audio, out_audio are Mx1, Nx1 matrix
@@ -79,55 +243,202 @@ where
     //
     // In math, the in_audio and out_audio should be a 2D-matrix with several rows containing only
     // one column. However, the in_audio and out_audio are passed by 1-D matrix here for convenience.
-    pub fn create(input_channels: &[Channel], output_channels: &[Channel]) -> Self {
-        let input_layout = ChannelLayout::new(input_channels).expect("Invalid input layout");
-        let output_layout = ChannelLayout::new(output_channels).expect("Invalid output layout");
-
-        let mixing_matrix =
-            Self::build_mixing_matrix(input_layout.channel_map, output_layout.channel_map)
-                .unwrap_or_else(|_| Self::get_basic_matrix());
+    pub fn create(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let input_layout = ChannelLayout::new(input_channels);
+        check_duplicate_channels(&input_layout, Side::Input)?;
+        let output_layout = ChannelLayout::new(output_channels);
+        check_duplicate_channels(&output_layout, Side::Output)?;
+
+        let mixing_matrix = Self::build_mixing_matrix(
+            input_layout.channel_map(),
+            output_layout.channel_map(),
+            config,
+        );
 
         let coefficient_matrix = Self::pick_coefficients(
-            &input_layout.channels,
-            &output_layout.channels,
+            input_layout.channels(),
+            output_layout.channels(),
             &mixing_matrix,
         );
 
-        let normalized_matrix = Self::normalize(T::max_coefficients_sum(), coefficient_matrix);
+        let normalization = config
+            .normalization()
+            .unwrap_or_else(T::default_normalization);
+        Ok(Self::with_matrix(
+            input_layout,
+            output_layout,
+            coefficient_matrix,
+            normalization,
+        ))
+    }
+
+    // Given a M-channel input layout, a N-channel output layout and a NxM coefficients matrix m,
+    // skip generating the matrix from the channel layouts and use m as the mixing matrix
+    // directly. The coefficients for the Silence channels are always zero, no matter what
+    // values are given in m. The matrix is still normalized by the default normalization of
+    // type T.
+    pub fn create_with_matrix(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        matrix: &[Vec<f64>],
+    ) -> Result<Self, MixerError> {
+        let input_layout = ChannelLayout::new(input_channels);
+        check_duplicate_channels(&input_layout, Side::Input)?;
+        let output_layout = ChannelLayout::new(output_channels);
+        check_duplicate_channels(&output_layout, Side::Output)?;
+
+        if matrix.len() != output_channels.len() {
+            return Err(MixerError::MatrixSizeMismatch {
+                side: Side::Output,
+                expected: output_channels.len(),
+                actual: matrix.len(),
+            });
+        }
+        let mut coefficient_matrix = Vec::with_capacity(matrix.len());
+        for (i, (row, output_channel)) in matrix.iter().zip(output_channels).enumerate() {
+            if row.len() != input_channels.len() {
+                return Err(MixerError::MatrixSizeMismatch {
+                    side: Side::Input,
+                    expected: input_channels.len(),
+                    actual: row.len(),
+                });
+            }
+            let mut coefficients = Vec::with_capacity(row.len());
+            for (j, (coef, input_channel)) in row.iter().zip(input_channels).enumerate() {
+                if !coef.is_finite() {
+                    return Err(MixerError::NonFiniteCoefficient { row: i, column: j });
+                }
+                coefficients.push(
+                    if input_channel == &Channel::Silence || output_channel == &Channel::Silence {
+                        0.0
+                    } else {
+                        *coef
+                    },
+                );
+            }
+            coefficient_matrix.push(coefficients);
+        }
+
+        Ok(Self::with_matrix(
+            input_layout,
+            output_layout,
+            coefficient_matrix,
+            T::default_normalization(),
+        ))
+    }
+
+    // Create the coefficient whose matrix is from_weight * from + to_weight * to, for the same
+    // layouts as from and to. The matrix is not normalized again.
+    pub fn interpolate(from: &Self, to: &Self, from_weight: f64, to_weight: f64) -> Self {
+        let matrix = from
+            .normalized_matrix
+            .iter()
+            .zip(to.normalized_matrix.iter())
+            .map(|(from_row, to_row)| {
+                from_row
+                    .iter()
+                    .zip(to_row.iter())
+                    .map(|(from_coef, to_coef)| from_weight * from_coef + to_weight * to_coef)
+                    .collect()
+            })
+            .collect();
+        Self::with_matrix(
+            to.input_layout.clone(),
+            to.output_layout.clone(),
+            matrix,
+            Normalization::None,
+        )
+    }
+
+    // Normalize the NxM coefficient matrix and convert it to T::Coef. The normalized matrix is
+    // scaled down further if its absolute row sums are too large for T::Coef to hold the mixed
+    // value.
+    fn with_matrix(
+        input_layout: ChannelLayout,
+        output_layout: ChannelLayout,
+        coefficient_matrix: Vec<Vec<f64>>,
+        normalization: Normalization,
+    ) -> Self {
+        let input_channels = input_layout
+            .iter()
+            .filter(|channel| **channel != Channel::Silence)
+            .count();
+        let normalized_matrix = Self::limit_absolute_sum(
+            T::max_coefficients_sum(),
+            Self::normalize_with(normalization, input_channels, coefficient_matrix),
+        );
 
         let would_overflow = T::would_overflow_from_coefficient_value(&normalized_matrix);
 
         // Convert the type of the coefficients from f64 to T::Coef.
-        let matrix = normalized_matrix
-            .into_iter()
-            .map(|row| row.into_iter().map(T::coefficient_from_f64).collect())
+        let matrix: Vec<T::Coef> = normalized_matrix
+            .iter()
+            .flat_map(|row| row.iter().copied().map(T::coefficient_from_f64))
             .collect();
+        let plan = Plan::compile(&normalized_matrix, &matrix);
 
         Self {
             input_layout,
             output_layout,
+            normalized_matrix,
             matrix,
             would_overflow_from_coefficient_value: would_overflow,
+            plan,
         }
     }
 
     // Return the coefficient for mixing input channel data into output channel.
     pub fn get(&self, input: usize, output: usize) -> T::Coef {
-        assert!(output < self.matrix.len());
-        assert!(input < self.matrix[output].len());
-        self.matrix[output][input] // Perform copy so T::Coef must implement Copy.
+        let input_channels = self.input_channels().len();
+        assert!(output < self.output_channels().len());
+        assert!(input < input_channels);
+        self.matrix[output * input_channels + input] // Perform copy so T::Coef must implement Copy.
+    }
+
+    // Return how the data of the output channel is mixed, from the compiled execution plan.
+    pub fn row(&self, output: usize) -> Row<'_, T::Coef> {
+        let (start, end) = self.plan.ranges[output];
+        if let Some(input) = self.plan.copies[output] {
+            Row::Copy(input)
+        } else if start == end {
+            Row::Zero
+        } else {
+            Row::Sum(&self.plan.triplets[start..end])
+        }
+    }
+
+    // Return the input channel copied to each output channel, or None for the silent ones, if
+    // the matrix only reorders the input channels, i.e., every output channel is either a copy
+    // or silent. Return None otherwise.
+    pub fn permutation(&self) -> Option<Vec<Option<usize>>> {
+        (0..self.plan.ranges.len())
+            .map(|output| match self.row(output) {
+                Row::Zero => Some(None),
+                Row::Copy(input) => Some(Some(input)),
+                Row::Sum(_) => None,
+            })
+            .collect()
     }
 
     pub fn would_overflow_from_coefficient_value(&self) -> Option<bool> {
         self.would_overflow_from_coefficient_value
     }
 
+    // Return the normalized NxM matrix in f64, before it's converted to T::Coef.
+    pub fn normalized_matrix(&self) -> &[Vec<f64>] {
+        &self.normalized_matrix
+    }
+
     pub fn input_channels(&self) -> &[Channel] {
-        &self.input_layout.channels
+        self.input_layout.channels()
     }
 
     pub fn output_channels(&self) -> &[Channel] {
-        &self.output_layout.channels
+        self.output_layout.channels()
     }
 
     // Given audio input and output channel-maps, generate a CxC mixing coefficients matrix M,
@@ -150,13 +461,15 @@ where
     fn build_mixing_matrix(
         input_map: ChannelMap,
         output_map: ChannelMap,
-    ) -> Result<[[f64; CHANNELS]; CHANNELS], Error> {
+        config: &MixerConfig,
+    ) -> [[f64; CHANNELS]; CHANNELS] {
         // Mixing coefficients constants.
         use std::f64::consts::FRAC_1_SQRT_2;
         use std::f64::consts::SQRT_2;
-        const CENTER_MIX_LEVEL: f64 = FRAC_1_SQRT_2;
-        const SURROUND_MIX_LEVEL: f64 = FRAC_1_SQRT_2;
-        const LFE_MIX_LEVEL: f64 = 1.0;
+        let center_mix_level = config.center_mix_level();
+        let surround_mix_level = config.surround_mix_level();
+        let lfe_mix_level = config.lfe_mix_level();
+        let height_mix_level = config.height_mix_level();
 
         // The indices of channels in the mixing coefficients matrix.
         const FRONT_LEFT: usize = Channel::FrontLeft.number();
@@ -170,22 +483,117 @@ where
         const BACK_CENTER: usize = Channel::BackCenter.number();
         const SIDE_LEFT: usize = Channel::SideLeft.number();
         const SIDE_RIGHT: usize = Channel::SideRight.number();
+        const TOP_CENTER: usize = Channel::TopCenter.number();
+        const TOP_FRONT_LEFT: usize = Channel::TopFrontLeft.number();
+        const TOP_FRONT_CENTER: usize = Channel::TopFrontCenter.number();
+        const TOP_FRONT_RIGHT: usize = Channel::TopFrontRight.number();
+        const TOP_BACK_LEFT: usize = Channel::TopBackLeft.number();
+        const TOP_BACK_CENTER: usize = Channel::TopBackCenter.number();
+        const TOP_BACK_RIGHT: usize = Channel::TopBackRight.number();
+
+        // The left and right channels of the symmetric pairs, and the center channel between them.
+        const SYMMETRIC_CHANNELS: [(Channel, Channel, Option<Channel>); 6] = [
+            (
+                Channel::FrontLeft,
+                Channel::FrontRight,
+                Some(Channel::FrontCenter),
+            ),
+            (
+                Channel::BackLeft,
+                Channel::BackRight,
+                Some(Channel::BackCenter),
+            ),
+            (
+                Channel::FrontLeftOfCenter,
+                Channel::FrontRightOfCenter,
+                Some(Channel::FrontCenter),
+            ),
+            (Channel::SideLeft, Channel::SideRight, None),
+            (
+                Channel::TopFrontLeft,
+                Channel::TopFrontRight,
+                Some(Channel::TopFrontCenter),
+            ),
+            (
+                Channel::TopBackLeft,
+                Channel::TopBackRight,
+                Some(Channel::TopBackCenter),
+            ),
+        ];
 
-        // Return true if mixable channels are symmetric.
-        fn is_symmetric(map: ChannelMap) -> bool {
-            fn even(map: ChannelMap) -> bool {
-                map.bits().count_ones() % 2 == 0
+        // Add the missing counterpart of every lone left or right channel into the map.
+        fn complete_symmetric_channels(mut map: ChannelMap) -> ChannelMap {
+            for (left, right, _) in SYMMETRIC_CHANNELS.iter() {
+                let pair = ChannelMap::from(*left) | ChannelMap::from(*right);
+                if map.intersects(pair) {
+                    map.insert(pair);
+                }
             }
-            even(map & ChannelMap::FRONT_2)
-                && even(map & ChannelMap::BACK_2)
-                && even(map & ChannelMap::FRONT_2_OF_CENTER)
-                && even(map & ChannelMap::SIDE_2)
+            map
         }
 
-        if !is_symmetric(input_map) || !is_symmetric(output_map) {
-            return Err(Error::AsymmetricChannels);
+        // The output channels on the same side as the given one, ordered by their proximity to it.
+        fn same_side_channels(channel: Channel) -> &'static [Channel] {
+            match channel {
+                Channel::FrontLeft => &[
+                    Channel::FrontLeftOfCenter,
+                    Channel::SideLeft,
+                    Channel::BackLeft,
+                ],
+                Channel::FrontRight => &[
+                    Channel::FrontRightOfCenter,
+                    Channel::SideRight,
+                    Channel::BackRight,
+                ],
+                Channel::BackLeft => &[Channel::SideLeft, Channel::FrontLeft],
+                Channel::BackRight => &[Channel::SideRight, Channel::FrontRight],
+                Channel::SideLeft => &[Channel::BackLeft, Channel::FrontLeft],
+                Channel::SideRight => &[Channel::BackRight, Channel::FrontRight],
+                Channel::FrontLeftOfCenter => {
+                    &[Channel::FrontLeft, Channel::SideLeft, Channel::BackLeft]
+                }
+                Channel::FrontRightOfCenter => {
+                    &[Channel::FrontRight, Channel::SideRight, Channel::BackRight]
+                }
+                Channel::TopFrontLeft => &[
+                    Channel::TopBackLeft,
+                    Channel::FrontLeft,
+                    Channel::SideLeft,
+                    Channel::BackLeft,
+                ],
+                Channel::TopFrontRight => &[
+                    Channel::TopBackRight,
+                    Channel::FrontRight,
+                    Channel::SideRight,
+                    Channel::BackRight,
+                ],
+                Channel::TopBackLeft => &[
+                    Channel::TopFrontLeft,
+                    Channel::BackLeft,
+                    Channel::SideLeft,
+                    Channel::FrontLeft,
+                ],
+                Channel::TopBackRight => &[
+                    Channel::TopFrontRight,
+                    Channel::BackRight,
+                    Channel::SideRight,
+                    Channel::FrontRight,
+                ],
+                _ => &[],
+            }
         }
 
+        // The rules below are designed for the symmetric layouts, so the asymmetric layouts are
+        // completed into symmetric ones first. The channels added to the input have no data, so
+        // their coefficients are merged into the lone surround channels at the end, and the
+        // other lone input channels are mixed on their own side as if their counterparts were
+        // silent. The channels added to the output have no buffer, so their coefficients are
+        // moved to the output channels at the end.
+        let real_input_map = input_map;
+        let real_output_map = output_map;
+        let input_map = complete_symmetric_channels(input_map);
+        let output_map = complete_symmetric_channels(output_map);
+
         let mut matrix = Self::get_basic_matrix();
 
         // Get input channels that are not in the output channels.
@@ -197,7 +605,7 @@ where
             && output_map.contains(ChannelMap::FRONT_2)
         {
             let coefficient = if input_map.contains(ChannelMap::FRONT_2) {
-                CENTER_MIX_LEVEL
+                center_mix_level
             } else {
                 FRAC_1_SQRT_2
             };
@@ -213,7 +621,7 @@ where
             matrix[FRONT_CENTER][FRONT_LEFT] += FRAC_1_SQRT_2;
             matrix[FRONT_CENTER][FRONT_RIGHT] += FRAC_1_SQRT_2;
             if input_map.contains(ChannelMap::FRONT_CENTER) {
-                matrix[FRONT_CENTER][FRONT_CENTER] = CENTER_MIX_LEVEL * SQRT_2;
+                matrix[FRONT_CENTER][FRONT_CENTER] = center_mix_level * SQRT_2;
             }
         }
 
@@ -229,11 +637,11 @@ where
                 matrix[SIDE_RIGHT][BACK_CENTER] += FRAC_1_SQRT_2;
             // or if output has front-stereo, mix input's back-center to output's front-stereo.
             } else if output_map.contains(ChannelMap::FRONT_2) {
-                matrix[FRONT_LEFT][BACK_CENTER] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
-                matrix[FRONT_RIGHT][BACK_CENTER] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
+                matrix[FRONT_LEFT][BACK_CENTER] += surround_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_RIGHT][BACK_CENTER] += surround_mix_level * FRAC_1_SQRT_2;
             // or if output has front-center, mix input's back-center to output's front-center.
             } else if output_map.contains(ChannelMap::FRONT_CENTER) {
-                matrix[FRONT_CENTER][BACK_CENTER] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][BACK_CENTER] += surround_mix_level * FRAC_1_SQRT_2;
             }
         }
 
@@ -254,12 +662,12 @@ where
                 matrix[SIDE_RIGHT][BACK_RIGHT] += coefficient;
             // or if output has front-stereo, mix input's back-stereo to output's side-stereo.
             } else if output_map.contains(ChannelMap::FRONT_2) {
-                matrix[FRONT_LEFT][BACK_LEFT] += SURROUND_MIX_LEVEL;
-                matrix[FRONT_RIGHT][BACK_RIGHT] += SURROUND_MIX_LEVEL;
+                matrix[FRONT_LEFT][BACK_LEFT] += surround_mix_level;
+                matrix[FRONT_RIGHT][BACK_RIGHT] += surround_mix_level;
             // or if output has front-center, mix input's back-stereo to output's front-center.
             } else if output_map.contains(ChannelMap::FRONT_CENTER) {
-                matrix[FRONT_CENTER][BACK_LEFT] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
-                matrix[FRONT_CENTER][BACK_RIGHT] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][BACK_LEFT] += surround_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][BACK_RIGHT] += surround_mix_level * FRAC_1_SQRT_2;
             }
         }
 
@@ -280,12 +688,12 @@ where
                 matrix[BACK_CENTER][SIDE_RIGHT] += FRAC_1_SQRT_2;
             // or if output has front-stereo, mix input's side-stereo to output's front-stereo.
             } else if output_map.contains(ChannelMap::FRONT_2) {
-                matrix[FRONT_LEFT][SIDE_LEFT] += SURROUND_MIX_LEVEL;
-                matrix[FRONT_RIGHT][SIDE_RIGHT] += SURROUND_MIX_LEVEL;
+                matrix[FRONT_LEFT][SIDE_LEFT] += surround_mix_level;
+                matrix[FRONT_RIGHT][SIDE_RIGHT] += surround_mix_level;
             // or if output has front-center, mix input's side-stereo to output's front-center.
             } else if output_map.contains(ChannelMap::FRONT_CENTER) {
-                matrix[FRONT_CENTER][SIDE_LEFT] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
-                matrix[FRONT_CENTER][SIDE_RIGHT] += SURROUND_MIX_LEVEL * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][SIDE_LEFT] += surround_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][SIDE_RIGHT] += surround_mix_level * FRAC_1_SQRT_2;
             }
         }
 
@@ -302,19 +710,187 @@ where
             }
         }
 
+        // When input has top-front-stereo but output has not,
+        if unaccounted_input_map.contains(ChannelMap::TOP_FRONT_2) {
+            // if output has front-stereo, mix input's top-front-stereo to output's front-stereo.
+            if output_map.contains(ChannelMap::FRONT_2) {
+                matrix[FRONT_LEFT][TOP_FRONT_LEFT] += height_mix_level;
+                matrix[FRONT_RIGHT][TOP_FRONT_RIGHT] += height_mix_level;
+            // or if output has front-center, mix input's top-front-stereo to output's front-center.
+            } else if output_map.contains(ChannelMap::FRONT_CENTER) {
+                matrix[FRONT_CENTER][TOP_FRONT_LEFT] += height_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][TOP_FRONT_RIGHT] += height_mix_level * FRAC_1_SQRT_2;
+            }
+        }
+
+        // When input has top-front-center but output has not,
+        if unaccounted_input_map.contains(ChannelMap::TOP_FRONT_CENTER) {
+            // if output has front-center, mix input's top-front-center to output's front-center.
+            if output_map.contains(ChannelMap::FRONT_CENTER) {
+                matrix[FRONT_CENTER][TOP_FRONT_CENTER] += height_mix_level;
+            // or if output has front-stereo, mix input's top-front-center to output's front-stereo.
+            } else if output_map.contains(ChannelMap::FRONT_2) {
+                matrix[FRONT_LEFT][TOP_FRONT_CENTER] += height_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_RIGHT][TOP_FRONT_CENTER] += height_mix_level * FRAC_1_SQRT_2;
+            }
+        }
+
+        // When input has top-back-stereo but output has not,
+        if unaccounted_input_map.contains(ChannelMap::TOP_BACK_2) {
+            // if output has back-stereo, mix input's top-back-stereo to output's back-stereo.
+            if output_map.contains(ChannelMap::BACK_2) {
+                matrix[BACK_LEFT][TOP_BACK_LEFT] += height_mix_level;
+                matrix[BACK_RIGHT][TOP_BACK_RIGHT] += height_mix_level;
+            // or if output has side-stereo, mix input's top-back-stereo to output's side-stereo.
+            } else if output_map.contains(ChannelMap::SIDE_2) {
+                matrix[SIDE_LEFT][TOP_BACK_LEFT] += height_mix_level;
+                matrix[SIDE_RIGHT][TOP_BACK_RIGHT] += height_mix_level;
+            // or if output has front-stereo, mix input's top-back-stereo to output's front-stereo.
+            } else if output_map.contains(ChannelMap::FRONT_2) {
+                matrix[FRONT_LEFT][TOP_BACK_LEFT] += height_mix_level * surround_mix_level;
+                matrix[FRONT_RIGHT][TOP_BACK_RIGHT] += height_mix_level * surround_mix_level;
+            // or if output has front-center, mix input's top-back-stereo to output's front-center.
+            } else if output_map.contains(ChannelMap::FRONT_CENTER) {
+                let coefficient = height_mix_level * surround_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_CENTER][TOP_BACK_LEFT] += coefficient;
+                matrix[FRONT_CENTER][TOP_BACK_RIGHT] += coefficient;
+            }
+        }
+
+        // When input has top-back-center but output has not,
+        if unaccounted_input_map.contains(ChannelMap::TOP_BACK_CENTER) {
+            // if output has back-center, mix input's top-back-center to output's back-center.
+            if output_map.contains(ChannelMap::BACK_CENTER) {
+                matrix[BACK_CENTER][TOP_BACK_CENTER] += height_mix_level;
+            // or if output has back-stereo, mix input's top-back-center to output's back-stereo.
+            } else if output_map.contains(ChannelMap::BACK_2) {
+                matrix[BACK_LEFT][TOP_BACK_CENTER] += height_mix_level * FRAC_1_SQRT_2;
+                matrix[BACK_RIGHT][TOP_BACK_CENTER] += height_mix_level * FRAC_1_SQRT_2;
+            // or if output has side-stereo, mix input's top-back-center to output's side-stereo.
+            } else if output_map.contains(ChannelMap::SIDE_2) {
+                matrix[SIDE_LEFT][TOP_BACK_CENTER] += height_mix_level * FRAC_1_SQRT_2;
+                matrix[SIDE_RIGHT][TOP_BACK_CENTER] += height_mix_level * FRAC_1_SQRT_2;
+            // or if output has front-stereo, mix input's top-back-center to output's front-stereo.
+            } else if output_map.contains(ChannelMap::FRONT_2) {
+                let coefficient = height_mix_level * surround_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_LEFT][TOP_BACK_CENTER] += coefficient;
+                matrix[FRONT_RIGHT][TOP_BACK_CENTER] += coefficient;
+            // or if output has front-center, mix input's top-back-center to output's front-center.
+            } else if output_map.contains(ChannelMap::FRONT_CENTER) {
+                matrix[FRONT_CENTER][TOP_BACK_CENTER] += height_mix_level * surround_mix_level;
+            }
+        }
+
+        // When input has top-center but output has not,
+        if unaccounted_input_map.contains(ChannelMap::TOP_CENTER) {
+            // if output has top-front-stereo or top-back-stereo, spread input's top-center to
+            // all of them evenly, or spread it to all of output's front-stereo, back-stereo and
+            // side-stereo with the height level. If none of them is in output, mix input's
+            // top-center to output's front-center.
+            let mut targets = Vec::new();
+            let mut level = 1.0;
+            for (map, pair) in &[
+                (ChannelMap::TOP_FRONT_2, [TOP_FRONT_LEFT, TOP_FRONT_RIGHT]),
+                (ChannelMap::TOP_BACK_2, [TOP_BACK_LEFT, TOP_BACK_RIGHT]),
+            ] {
+                if output_map.contains(*map) {
+                    targets.extend_from_slice(pair);
+                }
+            }
+            if targets.is_empty() {
+                level = height_mix_level;
+                for (map, pair) in &[
+                    (ChannelMap::FRONT_2, [FRONT_LEFT, FRONT_RIGHT]),
+                    (ChannelMap::BACK_2, [BACK_LEFT, BACK_RIGHT]),
+                    (ChannelMap::SIDE_2, [SIDE_LEFT, SIDE_RIGHT]),
+                ] {
+                    if output_map.contains(*map) {
+                        targets.extend_from_slice(pair);
+                    }
+                }
+            }
+            if targets.is_empty() && output_map.contains(ChannelMap::FRONT_CENTER) {
+                targets.push(FRONT_CENTER);
+            }
+            // Keep the power of the top-center channel when it's spread to several channels.
+            for target in &targets {
+                matrix[*target][TOP_CENTER] += level / (targets.len() as f64).sqrt();
+            }
+        }
+
         // When input has low-frequency but output has not,
         if unaccounted_input_map.contains(ChannelMap::LOW_FREQUENCY) {
             // if output has front-center, mix input's low-frequency to output's front-center.
             if output_map.contains(ChannelMap::FRONT_CENTER) {
-                matrix[FRONT_CENTER][LOW_FREQUENCY] += LFE_MIX_LEVEL;
+                matrix[FRONT_CENTER][LOW_FREQUENCY] += lfe_mix_level;
             // or if output has front-stereo, mix input's low-frequency to output's front-stereo.
             } else if output_map.contains(ChannelMap::FRONT_2) {
-                matrix[FRONT_LEFT][LOW_FREQUENCY] += LFE_MIX_LEVEL * FRAC_1_SQRT_2;
-                matrix[FRONT_RIGHT][LOW_FREQUENCY] += LFE_MIX_LEVEL * FRAC_1_SQRT_2;
+                matrix[FRONT_LEFT][LOW_FREQUENCY] += lfe_mix_level * FRAC_1_SQRT_2;
+                matrix[FRONT_RIGHT][LOW_FREQUENCY] += lfe_mix_level * FRAC_1_SQRT_2;
+            }
+        }
+
+        // When output has a lone left or right channel, move the coefficients of its missing
+        // counterpart to output's center channel between them, or to the nearest output channel
+        // on the same side, or to the lone channel itself if output has none of them.
+        let mut folded_rows = [false; CHANNELS];
+        for (left, right, center) in SYMMETRIC_CHANNELS.iter() {
+            for (added, counterpart) in &[(*left, *right), (*right, *left)] {
+                let added_map = ChannelMap::from(*added);
+                if real_output_map.contains(added_map) || !output_map.contains(added_map) {
+                    continue;
+                }
+                let same_side = same_side_channels(*added)
+                    .iter()
+                    .find(|channel| real_output_map.contains(ChannelMap::from(**channel)));
+                let (target, coefficient) = match (center, same_side) {
+                    (Some(center), _) if real_output_map.contains(ChannelMap::from(*center)) => {
+                        (*center, FRAC_1_SQRT_2)
+                    }
+                    (_, Some(channel)) => (*channel, 1.0),
+                    _ => {
+                        folded_rows[counterpart.number()] = true;
+                        (*counterpart, 1.0)
+                    }
+                };
+                let row = std::mem::replace(&mut matrix[added.number()], [0.0; CHANNELS]);
+                for (target_coef, coef) in matrix[target.number()].iter_mut().zip(row.iter()) {
+                    *target_coef += coef * coefficient;
+                }
+            }
+        }
+
+        // When input has a lone back or side channel that output has not, pan it across both
+        // sides by merging the coefficients of its missing counterpart into it, at -3 dB each.
+        // If both of them are already mixed to an output channel with its counterpart folded
+        // into it, the lone channel is on that side anyway, so the missing counterpart is just
+        // dropped instead.
+        for (left, right) in &[
+            (Channel::BackLeft, Channel::BackRight),
+            (Channel::SideLeft, Channel::SideRight),
+        ] {
+            for (lone, added) in &[(*left, *right), (*right, *left)] {
+                if !real_input_map.contains(ChannelMap::from(*lone))
+                    || real_input_map.contains(ChannelMap::from(*added))
+                    || real_output_map.contains(ChannelMap::from(*lone))
+                {
+                    continue;
+                }
+                let (lone, added) = (lone.number(), added.number());
+                let folded = matrix
+                    .iter()
+                    .zip(folded_rows.iter())
+                    .any(|(row, folded)| *folded && row[lone] != 0.0 && row[added] != 0.0);
+                for row in matrix.iter_mut() {
+                    if !folded {
+                        row[lone] = (row[lone] + row[added]) * FRAC_1_SQRT_2;
+                    }
+                    row[added] = 0.0;
+                }
             }
         }
 
-        Ok(matrix)
+        matrix
     }
 
     // Return a CHANNELSxCHANNELS matrix M that is (CHANNELS-1)x(CHANNELS-1) identity matrix
@@ -394,6 +970,61 @@ where
         matrix
     }
 
+    // Normalize the coefficients by the given mode. The input_channels is the number of the
+    // non-silence input channels, which the loudness-matched normalization is based on.
+    fn normalize_with(
+        normalization: Normalization,
+        input_channels: usize,
+        coefficients: Vec<Vec<f64>>,
+    ) -> Vec<Vec<f64>> {
+        match normalization {
+            Normalization::None => coefficients,
+            Normalization::PeakSafe => Self::normalize(1.0, coefficients),
+            Normalization::PowerPreserving => {
+                let max_norm = coefficients
+                    .iter()
+                    .map(|coefs| coefs.iter().map(|coef| coef * coef).sum::<f64>().sqrt())
+                    .fold(0.0, f64::max);
+                if max_norm > 1.0 {
+                    Self::scale(1.0 / max_norm, coefficients)
+                } else {
+                    coefficients
+                }
+            }
+            Normalization::LoudnessMatched => {
+                let power: f64 = coefficients.iter().flatten().map(|coef| coef * coef).sum();
+                if power > 0.0 {
+                    Self::scale((input_channels as f64 / power).sqrt(), coefficients)
+                } else {
+                    coefficients
+                }
+            }
+        }
+    }
+
+    // Scale the coefficients down so the maximum absolute row sum is not greater than the
+    // given one.
+    fn limit_absolute_sum(max_sum: f64, coefficients: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        let max_absolute_sum = coefficients
+            .iter()
+            .map(|coefs| coefs.iter().map(|coef| coef.abs()).sum::<f64>())
+            .fold(0.0, f64::max);
+        if max_absolute_sum > max_sum {
+            Self::scale(max_sum / max_absolute_sum, coefficients)
+        } else {
+            coefficients
+        }
+    }
+
+    fn scale(factor: f64, mut coefficients: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        for coefs in &mut coefficients {
+            for coef in coefs {
+                *coef *= factor;
+            }
+        }
+        coefficients
+    }
+
     fn normalize(max_coefficients_sum: f64, mut coefficients: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
         let mut max_sum: f64 = 0.0;
         for coefs in &coefficients {
@@ -411,26 +1042,98 @@ where
     }
 }
 
-pub trait MixingCoefficient {
-    type Coef;
+// The internal part of the Sample trait, implemented for the natively supported sample types.
+// It's not exported outside of the crate, so it can be changed without breaking the users and
+// the mixing arithmetic of the custom sample types is always done by the types here.
+pub trait MixingCoefficient: Copy + Debug + 'static {
+    type Coef: AddAssign + Copy + Debug + Default + Mul<Self::Coef, Output = Self::Coef>;
 
-    // TODO: These should be private.
-    fn max_coefficients_sum() -> f64; // Used for normalizing.
+    // The normalization used when it's not specified in the config.
+    fn default_normalization() -> Normalization;
+    // The maximum absolute row sum of the matrix that Self::Coef can hold the mixed value for.
+    fn max_coefficients_sum() -> f64;
     fn coefficient_from_f64(value: f64) -> Self::Coef;
     // Precheck if overflow occurs when converting value from Self::Coef type to Self type.
     fn would_overflow_from_coefficient_value(coefficient: &[Vec<f64>]) -> Option<bool>;
 
-    // Convert f32 (Self) -> f32 (Self::Coef) or i16 (Self) -> i32 (Self::Coef)
+    // Convert f32 (Self) -> f32 (Self::Coef), f64 (Self) -> f64 (Self::Coef),
+    // i16 or u8 (Self) -> i32 (Self::Coef) or i32 or 24-bit integer (Self) -> i64 (Self::Coef)
     #[allow(clippy::wrong_self_convention)]
     fn to_coefficient_value(value: Self) -> Self::Coef;
     fn from_coefficient_value(value: Self::Coef, would_overflow: Option<bool>) -> Self;
+    // Convert the mixed value in Self::Coef to f64 in units of the LSB of Self. It's the inverse
+    // of coefficient_from_f64, which is used to add the dither noise to the mixed value.
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64;
+    // Convert the sample between Self and f64 in units of the LSB of Self, the same unit as
+    // coefficient_value_to_f64, so the mixed values can be scaled and summed in f64. The f64
+    // value is rounded like from_coefficient_value and clipped into the range of Self.
+    fn to_mixed_f64(value: Self) -> f64;
+    fn from_mixed_f64(value: f64) -> Self;
+    // Add the mixed value in Self::Coef multiplied by the gain to the sample.
+    fn add_coefficient_value(sample: Self, value: Self::Coef, gain: f64) -> Self {
+        Self::from_mixed_f64(
+            Self::to_mixed_f64(sample) + gain * Self::coefficient_value_to_f64(value),
+        )
+    }
+
+    // Convert the sample between Self and f64, where the full scale of the integer types is
+    // mapped to [-1.0, 1.0). The value out of the range of Self is clipped.
+    fn to_f64_sample(value: Self) -> f64;
+    fn from_f64_sample(value: f64) -> Self;
+    // Convert the value mixed in f64, where the full scale is the same as to_f64_sample's, to the
+    // mixed value in Self::Coef. It's not clipped into the range of Self, but into the range
+    // Self::Coef can hold, so it can be quantized like the values mixed in Self::Coef.
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef;
+
+    // Convert the sample of type S to Self. The conversion is done by S::to_*_sample below,
+    // so the conversion from Self to Self can be overridden to copy the value directly.
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self;
+
+    // Return the samples as f32 or i16 if Self is the type, so they can be mixed by the SIMD
+    // kernels.
+    fn as_f32_slice(_values: &[Self]) -> Option<&[f32]> {
+        None
+    }
+    fn as_f32_slice_mut(_values: &mut [Self]) -> Option<&mut [f32]> {
+        None
+    }
+    fn as_i16_slice(_values: &[Self]) -> Option<&[i16]> {
+        None
+    }
+    fn as_i16_slice_mut(_values: &mut [Self]) -> Option<&mut [i16]> {
+        None
+    }
+
+    // Convert the sample from Self to the sample types implementing MixingCoefficient.
+    fn to_f32_sample(value: Self) -> f32 {
+        f32::from_f64_sample(Self::to_f64_sample(value))
+    }
+    fn to_i16_sample(value: Self) -> i16 {
+        i16::from_f64_sample(Self::to_f64_sample(value))
+    }
+    fn to_i32_sample(value: Self) -> i32 {
+        i32::from_f64_sample(Self::to_f64_sample(value))
+    }
+    fn to_u8_sample(value: Self) -> u8 {
+        u8::from_f64_sample(Self::to_f64_sample(value))
+    }
+    fn to_i24_sample(value: Self) -> I24 {
+        I24::from_f64_sample(Self::to_f64_sample(value))
+    }
+    fn to_i24_in_32_sample(value: Self) -> I24In32 {
+        I24In32::from_f64_sample(Self::to_f64_sample(value))
+    }
 }
 
 impl MixingCoefficient for f32 {
     type Coef = f32;
 
+    fn default_normalization() -> Normalization {
+        Normalization::None
+    }
+
     fn max_coefficients_sum() -> f64 {
-        f64::from(std::i32::MAX)
+        f64::from(i32::MAX)
     }
 
     fn coefficient_from_f64(value: f64) -> Self::Coef {
@@ -449,13 +1152,59 @@ impl MixingCoefficient for f32 {
         assert!(would_overflow.is_none());
         value
     }
+
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        f64::from(value)
+    }
+
+    fn to_mixed_f64(value: Self) -> f64 {
+        f64::from(value)
+    }
+
+    fn from_mixed_f64(value: f64) -> Self {
+        value as Self
+    }
+
+    fn to_f64_sample(value: Self) -> f64 {
+        f64::from(value)
+    }
+
+    fn from_f64_sample(value: f64) -> Self {
+        value as Self
+    }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        value as Self::Coef
+    }
+
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_f32_sample(value)
+    }
+
+    fn to_f32_sample(value: Self) -> f32 {
+        value
+    }
+
+    fn as_f32_slice(values: &[Self]) -> Option<&[f32]> {
+        Some(values)
+    }
+
+    fn as_f32_slice_mut(values: &mut [Self]) -> Option<&mut [f32]> {
+        Some(values)
+    }
 }
 
 impl MixingCoefficient for i16 {
     type Coef = i32;
 
+    fn default_normalization() -> Normalization {
+        Normalization::PeakSafe
+    }
+
+    // The mixed value of the 16-bit samples in Q15 is less than 2^31 if the absolute row sum is
+    // less than 2. Leave some headroom for the rounding.
     fn max_coefficients_sum() -> f64 {
-        1.0
+        1.99
     }
 
     fn coefficient_from_f64(value: f64) -> Self::Coef {
@@ -463,19 +1212,7 @@ impl MixingCoefficient for i16 {
     }
 
     fn would_overflow_from_coefficient_value(coefficient: &[Vec<f64>]) -> Option<bool> {
-        let mut max_sum: Self::Coef = 0;
-        for row in coefficient {
-            let mut sum: Self::Coef = 0;
-            let mut rem: f64 = 0.0;
-            for coef in row {
-                let target = coef * f64::from(1 << 15) + rem;
-                let value = target.round() as Self::Coef;
-                rem += target - target.round();
-                sum += value.abs();
-            }
-            max_sum = max_sum.max(sum);
-        }
-        Some(max_sum > (1 << 15))
+        Some(max_fixed_point_sum(coefficient, 15) > (1 << 15))
     }
 
     fn to_coefficient_value(value: Self) -> Self::Coef {
@@ -492,117 +1229,600 @@ impl MixingCoefficient for i16 {
         }
         Self::try_from(converted).expect("Cannot convert coefficient from i32 to i16")
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        f64::from(value) / f64::from(1 << 15)
+    }
 
-    #[test]
-    fn test_create_f32() {
-        test_create::<f32>(MixDirection::Downmix);
-        test_create::<f32>(MixDirection::Upmix);
+    fn to_mixed_f64(value: Self) -> f64 {
+        f64::from(value)
     }
 
-    #[test]
-    fn test_create_i16() {
-        test_create::<i16>(MixDirection::Downmix);
-        test_create::<i16>(MixDirection::Upmix);
+    // The float-to-int cast saturates the value into the range of Self.
+    fn from_mixed_f64(value: f64) -> Self {
+        (value + 0.5).floor() as Self
     }
 
-    fn test_create<T>(direction: MixDirection)
-    where
-        T: MixingCoefficient,
-        T::Coef: Copy + Debug,
-    {
-        let (input_channels, output_channels) = get_test_channels(direction);
-        let coefficient = Coefficient::<T>::create(&input_channels, &output_channels);
-        println!(
-            "{:?} = {:?} * {:?}",
-            output_channels, coefficient.matrix, input_channels
-        );
+    fn to_f64_sample(value: Self) -> f64 {
+        f64::from(value) / f64::from(1 << 15)
     }
 
-    enum MixDirection {
-        Downmix,
-        Upmix,
+    // The float-to-int cast saturates the value into the range of Self.
+    fn from_f64_sample(value: f64) -> Self {
+        (value * f64::from(1 << 15)).round() as Self
     }
-    fn get_test_channels(direction: MixDirection) -> (Vec<Channel>, Vec<Channel>) {
-        let more = vec![
-            Channel::Silence,
-            Channel::FrontRight,
-            Channel::FrontLeft,
-            Channel::LowFrequency,
-            Channel::Silence,
-            Channel::BackCenter,
-        ];
-        let less = vec![
-            Channel::FrontLeft,
-            Channel::Silence,
-            Channel::FrontRight,
-            Channel::FrontCenter,
-        ];
-        match direction {
-            MixDirection::Downmix => (more, less),
-            MixDirection::Upmix => (less, more),
-        }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        Self::coefficient_from_f64(clamp_mixed_f64_sample(value) * f64::from(1 << 15))
     }
 
-    #[test]
-    fn test_create_with_duplicate_silience_channels_f32() {
-        test_create_with_duplicate_silience_channels::<f32>()
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_i16_sample(value)
     }
 
-    #[test]
-    fn test_create_with_duplicate_silience_channels_i16() {
-        test_create_with_duplicate_silience_channels::<i16>()
+    fn to_i16_sample(value: Self) -> i16 {
+        value
     }
 
-    #[test]
-    #[should_panic]
-    fn test_create_with_duplicate_input_channels_f32() {
-        test_create_with_duplicate_input_channels::<f32>()
+    fn as_i16_slice(values: &[Self]) -> Option<&[i16]> {
+        Some(values)
     }
 
-    #[test]
-    #[should_panic]
-    fn test_create_with_duplicate_input_channels_i16() {
-        test_create_with_duplicate_input_channels::<i16>()
+    fn as_i16_slice_mut(values: &mut [Self]) -> Option<&mut [i16]> {
+        Some(values)
     }
+}
 
-    #[test]
-    #[should_panic]
-    fn test_create_with_duplicate_output_channels_f32() {
-        test_create_with_duplicate_output_channels::<f32>()
+impl MixingCoefficient for i32 {
+    type Coef = i64;
+
+    fn default_normalization() -> Normalization {
+        Normalization::PeakSafe
     }
 
-    #[test]
-    #[should_panic]
-    fn test_create_with_duplicate_output_channels_i16() {
-        test_create_with_duplicate_output_channels::<i16>()
+    // The mixed value of the 32-bit samples in Q31 is less than 2^63 if the absolute row sum is
+    // less than 2. Leave some headroom for the rounding.
+    fn max_coefficients_sum() -> f64 {
+        1.99
     }
 
-    fn test_create_with_duplicate_silience_channels<T>()
-    where
-        T: MixingCoefficient,
-        T::Coef: Copy,
-    {
-        // Duplicate of Silence channels is allowed on both input side and output side.
-        let input_channels = [
-            Channel::FrontLeft,
-            Channel::Silence,
-            Channel::FrontRight,
-            Channel::FrontCenter,
-            Channel::Silence,
-        ];
-        let output_channels = [
-            Channel::Silence,
-            Channel::FrontRight,
-            Channel::FrontLeft,
+    fn coefficient_from_f64(value: f64) -> Self::Coef {
+        (value * (1_i64 << 31) as f64).round() as Self::Coef
+    }
+
+    fn would_overflow_from_coefficient_value(coefficient: &[Vec<f64>]) -> Option<bool> {
+        Some(max_fixed_point_sum(coefficient, 31) > (1 << 31))
+    }
+
+    fn to_coefficient_value(value: Self) -> Self::Coef {
+        Self::Coef::from(value)
+    }
+
+    fn from_coefficient_value(value: Self::Coef, would_overflow: Option<bool>) -> Self {
+        use std::convert::TryFrom;
+        let would_overflow = would_overflow.expect("would_overflow must have value for i32 type");
+        let mut converted = (value + (1 << 30)) >> 31;
+        // clip the signed integer value into the -2147483648,2147483647 range.
+        if would_overflow && ((converted + 0x8000_0000) & !0xFFFF_FFFF != 0) {
+            converted = (converted >> 63) ^ 0x7FFF_FFFF;
+        }
+        Self::try_from(converted).expect("Cannot convert coefficient from i64 to i32")
+    }
+
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        value as f64 / (1_i64 << 31) as f64
+    }
+
+    fn to_mixed_f64(value: Self) -> f64 {
+        f64::from(value)
+    }
+
+    // The float-to-int cast saturates the value into the range of Self.
+    fn from_mixed_f64(value: f64) -> Self {
+        (value + 0.5).floor() as Self
+    }
+
+    fn to_f64_sample(value: Self) -> f64 {
+        f64::from(value) / (1_i64 << 31) as f64
+    }
+
+    // The float-to-int cast saturates the value into the range of Self.
+    fn from_f64_sample(value: f64) -> Self {
+        (value * (1_i64 << 31) as f64).round() as Self
+    }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        Self::coefficient_from_f64(clamp_mixed_f64_sample(value) * (1_i64 << 31) as f64)
+    }
+
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_i32_sample(value)
+    }
+
+    fn to_i32_sample(value: Self) -> i32 {
+        value
+    }
+}
+
+impl MixingCoefficient for u8 {
+    type Coef = i32;
+
+    fn default_normalization() -> Normalization {
+        Normalization::PeakSafe
+    }
+
+    // The mixed value of the 8-bit samples in Q15 is less than 2^31 if the absolute row sum is
+    // less than 2^9.
+    fn max_coefficients_sum() -> f64 {
+        511.0
+    }
+
+    // The coefficients are in Q15 format, the same as i16's, for better precision.
+    fn coefficient_from_f64(value: f64) -> Self::Coef {
+        (value * f64::from(1 << 15)).round() as Self::Coef
+    }
+
+    fn would_overflow_from_coefficient_value(coefficient: &[Vec<f64>]) -> Option<bool> {
+        Some(max_fixed_point_sum(coefficient, 15) > (1 << 15))
+    }
+
+    // The unsigned 8-bit sample is offset binary, so 128 is the zero level.
+    fn to_coefficient_value(value: Self) -> Self::Coef {
+        Self::Coef::from(value) - 128
+    }
+
+    fn from_coefficient_value(value: Self::Coef, would_overflow: Option<bool>) -> Self {
+        use std::convert::TryFrom;
+        let would_overflow = would_overflow.expect("would_overflow must have value for u8 type");
+        let mut converted = (value + (1 << 14)) >> 15;
+        // clip the signed integer value into the -128,127 range.
+        if would_overflow && ((converted + 0x80) & !0xFF != 0) {
+            converted = (converted >> 31) ^ 0x7F;
+        }
+        Self::try_from(converted + 128).expect("Cannot convert coefficient from i32 to u8")
+    }
+
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        f64::from(value) / f64::from(1 << 15)
+    }
+
+    fn to_mixed_f64(value: Self) -> f64 {
+        f64::from(value) - 128.0
+    }
+
+    // The float-to-int cast saturates the value into the range of Self.
+    fn from_mixed_f64(value: f64) -> Self {
+        ((value + 0.5).floor() + 128.0) as Self
+    }
+
+    fn to_f64_sample(value: Self) -> f64 {
+        (f64::from(value) - 128.0) / 128.0
+    }
+
+    // The float-to-int cast saturates the value into the range of Self.
+    fn from_f64_sample(value: f64) -> Self {
+        ((value * 128.0).round() + 128.0) as Self
+    }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        Self::coefficient_from_f64(clamp_mixed_f64_sample(value) * 128.0)
+    }
+
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_u8_sample(value)
+    }
+
+    fn to_u8_sample(value: Self) -> u8 {
+        value
+    }
+}
+
+impl MixingCoefficient for f64 {
+    type Coef = f64;
+
+    fn default_normalization() -> Normalization {
+        Normalization::None
+    }
+
+    fn max_coefficients_sum() -> f64 {
+        f64::from(i32::MAX)
+    }
+
+    fn coefficient_from_f64(value: f64) -> Self::Coef {
+        value
+    }
+
+    fn would_overflow_from_coefficient_value(_coefficient: &[Vec<f64>]) -> Option<bool> {
+        None
+    }
+
+    fn to_coefficient_value(value: Self) -> Self::Coef {
+        value
+    }
+
+    fn from_coefficient_value(value: Self::Coef, would_overflow: Option<bool>) -> Self {
+        assert!(would_overflow.is_none());
+        value
+    }
+
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        value
+    }
+
+    fn to_mixed_f64(value: Self) -> f64 {
+        value
+    }
+
+    fn from_mixed_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f64_sample(value: Self) -> f64 {
+        value
+    }
+
+    fn from_f64_sample(value: f64) -> Self {
+        value
+    }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        value
+    }
+
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_f64_sample(value)
+    }
+}
+
+impl MixingCoefficient for I24 {
+    type Coef = i64;
+
+    fn default_normalization() -> Normalization {
+        Normalization::PeakSafe
+    }
+
+    // The mixed value of the 24-bit samples in Q23 is less than 2^63 if the absolute row sum is
+    // less than 2^17.
+    fn max_coefficients_sum() -> f64 {
+        131_071.0
+    }
+
+    fn coefficient_from_f64(value: f64) -> Self::Coef {
+        i24_coefficient_from_f64(value)
+    }
+
+    fn would_overflow_from_coefficient_value(coefficient: &[Vec<f64>]) -> Option<bool> {
+        Some(max_fixed_point_sum(coefficient, 23) > (1 << 23))
+    }
+
+    fn to_coefficient_value(value: Self) -> Self::Coef {
+        Self::Coef::from(value.to_i32())
+    }
+
+    fn from_coefficient_value(value: Self::Coef, would_overflow: Option<bool>) -> Self {
+        let would_overflow = would_overflow.expect("would_overflow must have value for I24 type");
+        Self::new(i24_from_coefficient_value(value, would_overflow))
+    }
+
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        value as f64 / f64::from(1 << 23)
+    }
+
+    fn to_mixed_f64(value: Self) -> f64 {
+        f64::from(value.to_i32())
+    }
+
+    fn from_mixed_f64(value: f64) -> Self {
+        Self::new(i24_from_mixed_f64(value))
+    }
+
+    fn to_f64_sample(value: Self) -> f64 {
+        f64::from(value.to_i32()) / f64::from(1 << 23)
+    }
+
+    fn from_f64_sample(value: f64) -> Self {
+        Self::new(i24_from_f64_sample(value))
+    }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        i24_coefficient_from_f64(clamp_mixed_f64_sample(value) * f64::from(1 << 23))
+    }
+
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_i24_sample(value)
+    }
+
+    fn to_i24_sample(value: Self) -> I24 {
+        value
+    }
+
+    // Only the sign extension is needed between the 24-bit sample types.
+    fn to_i24_in_32_sample(value: Self) -> I24In32 {
+        I24In32::new(value.to_i32())
+    }
+}
+
+impl MixingCoefficient for I24In32 {
+    type Coef = i64;
+
+    fn default_normalization() -> Normalization {
+        Normalization::PeakSafe
+    }
+
+    // The mixed value of the 24-bit samples in Q23 is less than 2^63 if the absolute row sum is
+    // less than 2^17.
+    fn max_coefficients_sum() -> f64 {
+        131_071.0
+    }
+
+    fn coefficient_from_f64(value: f64) -> Self::Coef {
+        i24_coefficient_from_f64(value)
+    }
+
+    fn would_overflow_from_coefficient_value(coefficient: &[Vec<f64>]) -> Option<bool> {
+        Some(max_fixed_point_sum(coefficient, 23) > (1 << 23))
+    }
+
+    fn to_coefficient_value(value: Self) -> Self::Coef {
+        Self::Coef::from(value.to_i32())
+    }
+
+    fn from_coefficient_value(value: Self::Coef, would_overflow: Option<bool>) -> Self {
+        let would_overflow =
+            would_overflow.expect("would_overflow must have value for I24In32 type");
+        Self::new(i24_from_coefficient_value(value, would_overflow))
+    }
+
+    fn coefficient_value_to_f64(value: Self::Coef) -> f64 {
+        value as f64 / f64::from(1 << 23)
+    }
+
+    fn to_mixed_f64(value: Self) -> f64 {
+        f64::from(value.to_i32())
+    }
+
+    fn from_mixed_f64(value: f64) -> Self {
+        Self::new(i24_from_mixed_f64(value))
+    }
+
+    fn to_f64_sample(value: Self) -> f64 {
+        f64::from(value.to_i32()) / f64::from(1 << 23)
+    }
+
+    fn from_f64_sample(value: f64) -> Self {
+        Self::new(i24_from_f64_sample(value))
+    }
+
+    fn coefficient_value_from_f64_sample(value: f64) -> Self::Coef {
+        i24_coefficient_from_f64(clamp_mixed_f64_sample(value) * f64::from(1 << 23))
+    }
+
+    fn from_sample<S: MixingCoefficient>(value: S) -> Self {
+        S::to_i24_in_32_sample(value)
+    }
+
+    fn to_i24_in_32_sample(value: Self) -> I24In32 {
+        value
+    }
+
+    // Only the sign extension is needed between the 24-bit sample types.
+    fn to_i24_sample(value: Self) -> I24 {
+        I24::new(value.to_i32())
+    }
+}
+
+// Clamp the value mixed in f64 into the range the mixed values of the integer types can hold. It's
+// far enough from the full scale, so the clipping of the output samples is not affected.
+fn clamp_mixed_f64_sample(value: f64) -> f64 {
+    value.clamp(-1.99, 1.99)
+}
+
+// The coefficients for the 24-bit samples are in Q23 format. The i64 mixing result can hold the
+// sum of the products of any 24-bit samples and Q23 coefficients without overflow.
+fn i24_coefficient_from_f64(value: f64) -> i64 {
+    (value * f64::from(1 << 23)).round() as i64
+}
+
+fn i24_from_coefficient_value(value: i64, would_overflow: bool) -> i32 {
+    use std::convert::TryFrom;
+    let mut converted = (value + (1 << 22)) >> 23;
+    // clip the signed integer value into the -8388608,8388607 range.
+    if would_overflow && ((converted + 0x80_0000) & !0xFF_FFFF != 0) {
+        converted = (converted >> 63) ^ 0x7F_FFFF;
+    }
+    i32::try_from(converted).expect("Cannot convert coefficient from i64 to 24-bit integer")
+}
+
+fn i24_from_mixed_f64(value: f64) -> i32 {
+    saturate_i24((value + 0.5).floor())
+}
+
+fn i24_from_f64_sample(value: f64) -> i32 {
+    saturate_i24((value * f64::from(1 << 23)).round())
+}
+
+// Saturate the rounded value into the 24-bit range.
+fn saturate_i24(value: f64) -> i32 {
+    value.clamp(f64::from(I24::MIN), f64::from(I24::MAX)) as i32
+}
+
+// Return the maximum of the absolute row sums of the coefficient matrix, after the coefficients
+// are converted to the fixed-point numbers with the given fractional bits. The rounding errors
+// are carried over to the next coefficient in the same row.
+fn max_fixed_point_sum(coefficient: &[Vec<f64>], fractional_bits: u32) -> i64 {
+    let one = (1_i64 << fractional_bits) as f64;
+    let mut max_sum: i64 = 0;
+    for row in coefficient {
+        let mut sum: i64 = 0;
+        let mut rem: f64 = 0.0;
+        for coef in row {
+            let target = coef * one + rem;
+            let value = target.round() as i64;
+            rem += target - target.round();
+            sum += value.abs();
+        }
+        max_sum = max_sum.max(sum);
+    }
+    max_sum
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    impl<T: MixingCoefficient> Coefficient<T> {
+        // Return the NxM matrix in T::Coef as rows.
+        fn matrix(&self) -> Vec<Vec<T::Coef>> {
+            self.matrix
+                .chunks(self.input_channels().len())
+                .map(|row| row.to_vec())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_create_f32() {
+        test_create::<f32>(MixDirection::Downmix);
+        test_create::<f32>(MixDirection::Upmix);
+    }
+
+    #[test]
+    fn test_create_i16() {
+        test_create::<i16>(MixDirection::Downmix);
+        test_create::<i16>(MixDirection::Upmix);
+    }
+
+    #[test]
+    fn test_create_i32() {
+        test_create::<i32>(MixDirection::Downmix);
+        test_create::<i32>(MixDirection::Upmix);
+    }
+
+    #[test]
+    fn test_create_u8() {
+        test_create::<u8>(MixDirection::Downmix);
+        test_create::<u8>(MixDirection::Upmix);
+    }
+
+    #[test]
+    fn test_create_f64() {
+        test_create::<f64>(MixDirection::Downmix);
+        test_create::<f64>(MixDirection::Upmix);
+    }
+
+    fn test_create<T>(direction: MixDirection)
+    where
+        T: MixingCoefficient,
+        T::Coef: Copy + Debug,
+    {
+        let (input_channels, output_channels) = get_test_channels(direction);
+        let coefficient =
+            Coefficient::<T>::create(&input_channels, &output_channels, &MixerConfig::default())
+                .unwrap();
+        println!(
+            "{:?} = {:?} * {:?}",
+            output_channels,
+            coefficient.matrix(),
+            input_channels
+        );
+    }
+
+    enum MixDirection {
+        Downmix,
+        Upmix,
+    }
+    fn get_test_channels(direction: MixDirection) -> (Vec<Channel>, Vec<Channel>) {
+        let more = vec![
+            Channel::Silence,
+            Channel::FrontRight,
+            Channel::FrontLeft,
+            Channel::LowFrequency,
+            Channel::Silence,
             Channel::BackCenter,
+        ];
+        let less = vec![
+            Channel::FrontLeft,
             Channel::Silence,
+            Channel::FrontRight,
+            Channel::FrontCenter,
         ];
-        let _ = Coefficient::<T>::create(&input_channels, &output_channels);
+        match direction {
+            MixDirection::Downmix => (more, less),
+            MixDirection::Upmix => (less, more),
+        }
+    }
+
+    #[test]
+    fn test_create_with_duplicate_silience_channels_f32() {
+        test_create_with_duplicate_silience_channels::<f32>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_silience_channels_i16() {
+        test_create_with_duplicate_silience_channels::<i16>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_silience_channels_i32() {
+        test_create_with_duplicate_silience_channels::<i32>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_input_channels_f32() {
+        test_create_with_duplicate_input_channels::<f32>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_input_channels_i16() {
+        test_create_with_duplicate_input_channels::<i16>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_input_channels_i32() {
+        test_create_with_duplicate_input_channels::<i32>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_output_channels_f32() {
+        test_create_with_duplicate_output_channels::<f32>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_output_channels_i16() {
+        test_create_with_duplicate_output_channels::<i16>()
+    }
+
+    #[test]
+    fn test_create_with_duplicate_output_channels_i32() {
+        test_create_with_duplicate_output_channels::<i32>()
+    }
+
+    fn test_create_with_duplicate_silience_channels<T>()
+    where
+        T: MixingCoefficient,
+        T::Coef: Copy,
+    {
+        // Duplicate of Silence channels is allowed on both input side and output side.
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::Silence,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::Silence,
+        ];
+        let output_channels = [
+            Channel::Silence,
+            Channel::FrontRight,
+            Channel::FrontLeft,
+            Channel::BackCenter,
+            Channel::Silence,
+        ];
+        assert!(Coefficient::<T>::create(
+            &input_channels,
+            &output_channels,
+            &MixerConfig::default()
+        )
+        .is_ok());
     }
 
     fn test_create_with_duplicate_input_channels<T>()
@@ -623,7 +1843,14 @@ mod test {
             Channel::FrontCenter,
             Channel::BackCenter,
         ];
-        let _ = Coefficient::<T>::create(&input_channels, &output_channels);
+        assert_eq!(
+            Coefficient::<T>::create(&input_channels, &output_channels, &MixerConfig::default())
+                .err(),
+            Some(MixerError::DuplicateNonSilenceChannel {
+                side: Side::Input,
+                channel: Channel::FrontLeft,
+            })
+        );
     }
 
     fn test_create_with_duplicate_output_channels<T>()
@@ -645,7 +1872,14 @@ mod test {
             Channel::FrontCenter,
             Channel::BackCenter,
         ];
-        let _ = Coefficient::<T>::create(&input_channels, &output_channels);
+        assert_eq!(
+            Coefficient::<T>::create(&input_channels, &output_channels, &MixerConfig::default())
+                .err(),
+            Some(MixerError::DuplicateNonSilenceChannel {
+                side: Side::Output,
+                channel: Channel::FrontCenter,
+            })
+        );
     }
 
     #[test]
@@ -658,6 +1892,21 @@ mod test {
         test_get_redirect_matrix::<i16>();
     }
 
+    #[test]
+    fn test_get_redirect_matrix_i32() {
+        test_get_redirect_matrix::<i32>();
+    }
+
+    #[test]
+    fn test_get_redirect_matrix_u8() {
+        test_get_redirect_matrix::<u8>();
+    }
+
+    #[test]
+    fn test_get_redirect_matrix_f64() {
+        test_get_redirect_matrix::<f64>();
+    }
+
     fn test_get_redirect_matrix<T>()
     where
         T: MixingCoefficient,
@@ -709,17 +1958,440 @@ mod test {
             Channel::Silence,
             Channel::FrontCenter,
             Channel::BackCenter,
+            Channel::FrontRight,
         ];
 
-        // Get a redirect matrix since the output layout is asymmetric.
-        let coefficient = Coefficient::<T>::create(&input_channels, &output_channels);
+        // Get a redirect matrix since all the input channels are in the output layout.
+        let coefficient =
+            Coefficient::<T>::create(&input_channels, &output_channels, &MixerConfig::default())
+                .unwrap();
 
         let expected = compute_redirect_matrix::<T>(&input_channels, &output_channels);
-        assert_eq!(coefficient.matrix, expected);
+        assert_eq!(coefficient.matrix(), expected);
 
         println!(
             "{:?} = {:?} * {:?}",
-            output_channels, coefficient.matrix, input_channels
+            output_channels,
+            coefficient.matrix(),
+            input_channels
+        );
+    }
+
+    #[test]
+    fn test_create_with_asymmetric_channels() {
+        use std::f32::consts::FRAC_1_SQRT_2;
+
+        let create = |input_channels: &[Channel], output_channels: &[Channel]| {
+            Coefficient::<f32>::create(input_channels, output_channels, &MixerConfig::default())
+                .unwrap()
+                .matrix()
+        };
+        let stereo = [Channel::FrontLeft, Channel::FrontRight];
+
+        // A lone surround channel is panned across both sides.
+        assert_eq!(
+            create(&[Channel::BackLeft], &stereo),
+            vec![vec![0.5], vec![0.5]]
+        );
+        assert_eq!(
+            create(
+                &[
+                    Channel::FrontLeft,
+                    Channel::FrontCenter,
+                    Channel::FrontRight,
+                    Channel::BackRight
+                ],
+                &stereo
+            ),
+            vec![
+                vec![1.0, FRAC_1_SQRT_2, 0.0, 0.5],
+                vec![0.0, FRAC_1_SQRT_2, 1.0, 0.5]
+            ]
+        );
+
+        // A lone front channel is kept on its own side.
+        assert_eq!(
+            create(&[Channel::FrontRight], &stereo),
+            vec![vec![0.0], vec![1.0]]
+        );
+
+        // The input channel whose counterpart is the only one in the output is mixed to the
+        // center channel between them, or to the output channel on its own side, or to its
+        // counterpart if there are neither.
+        assert_eq!(
+            create(&stereo, &[Channel::FrontLeft, Channel::FrontCenter]),
+            vec![vec![1.0, 0.0], vec![0.0, FRAC_1_SQRT_2]]
+        );
+        assert_eq!(create(&stereo, &[Channel::FrontLeft]), vec![vec![1.0, 1.0]]);
+        assert_eq!(
+            create(
+                &[Channel::SideLeft, Channel::SideRight],
+                &[Channel::FrontLeft, Channel::FrontRight, Channel::SideRight]
+            ),
+            vec![vec![1.0, 0.0], vec![0.0, 0.0], vec![0.0, 1.0]]
+        );
+
+        // The gain of each asymmetric case before the normalization. A lone surround channel is
+        // either panned across both sides or folded into its counterpart, but never both.
+        let gains = |input_channels: &[Channel], output_channels: &[Channel]| {
+            let config = MixerConfig::default().with_normalization(Normalization::None);
+            Coefficient::<f32>::create(input_channels, output_channels, &config)
+                .unwrap()
+                .matrix()
+        };
+        assert_eq!(
+            gains(&[Channel::BackLeft], &stereo),
+            vec![vec![0.5], vec![0.5]]
+        );
+        assert_eq!(
+            gains(&[Channel::BackLeft], &[Channel::BackCenter]),
+            vec![vec![1.0]]
+        );
+        assert_eq!(
+            gains(&[Channel::BackLeft], &[Channel::BackRight]),
+            vec![vec![1.0]]
+        );
+        assert_eq!(
+            gains(&[Channel::SideRight], &[Channel::SideLeft]),
+            vec![vec![1.0]]
+        );
+        assert_eq!(
+            gains(&[Channel::BackLeft], &[Channel::SideRight]),
+            vec![vec![1.0]]
+        );
+        assert_eq!(
+            gains(
+                &[Channel::FrontLeft, Channel::FrontRight, Channel::BackLeft],
+                &[Channel::FrontRight]
+            ),
+            vec![vec![1.0, 1.0, FRAC_1_SQRT_2]]
+        );
+        assert_eq!(
+            gains(
+                &[Channel::BackLeft],
+                &[Channel::FrontLeft, Channel::FrontRight, Channel::BackRight]
+            ),
+            vec![vec![FRAC_1_SQRT_2], vec![0.0], vec![FRAC_1_SQRT_2]]
+        );
+    }
+
+    #[test]
+    fn test_create_with_config() {
+        use crate::config::gain_from_db;
+        use float_cmp::approx_eq;
+
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let center_mix_level = gain_from_db(-4.5);
+        let surround_mix_level = gain_from_db(-6.0);
+        let config = MixerConfig::default()
+            .with_center_mix_level(center_mix_level)
+            .with_surround_mix_level(surround_mix_level)
+            .with_lfe_mix_level(0.0);
+
+        // f32 coefficients are not normalized, so they are the levels in config.
+        let coefficient =
+            Coefficient::<f32>::create(&input_channels, &output_channels, &config).unwrap();
+        let expected = [
+            [1.0, 0.0, center_mix_level, 0.0, surround_mix_level, 0.0],
+            [0.0, 1.0, center_mix_level, 0.0, 0.0, surround_mix_level],
+        ];
+        for (row, expected_row) in coefficient.matrix().iter().zip(expected.iter()) {
+            for (coef, expected_coef) in row.iter().zip(expected_row.iter()) {
+                assert!(approx_eq!(f32, *coef, *expected_coef as f32));
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        // The silent output channel is zero, and the channels existing in both sides are copied.
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let output_channels = [Channel::FrontLeft, Channel::Silence, Channel::FrontRight];
+        let coefficient =
+            Coefficient::<i16>::create(&input_channels, &output_channels, &MixerConfig::default())
+                .unwrap();
+        assert_eq!(coefficient.row(0), Row::Copy(0));
+        assert_eq!(coefficient.row(1), Row::Zero);
+        assert_eq!(coefficient.row(2), Row::Copy(1));
+
+        // Only the non-zero coefficients are in the triplets.
+        let matrix = vec![
+            vec![0.5, 0.0, 0.25],
+            vec![0.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0],
+        ];
+        let coefficient = Coefficient::<f32>::create_with_matrix(
+            &[Channel::FrontLeft, Channel::FrontRight, Channel::Silence],
+            &[
+                Channel::FrontLeft,
+                Channel::FrontRight,
+                Channel::FrontCenter,
+            ],
+            &matrix,
+        )
+        .unwrap();
+        assert_eq!(
+            coefficient.row(0),
+            Row::Sum(&[Triplet {
+                input: 0,
+                output: 0,
+                coefficient: 0.5,
+            }])
+        );
+        assert_eq!(coefficient.row(1), Row::Zero);
+        assert_eq!(coefficient.row(2), Row::Copy(1));
+        assert_eq!(coefficient.plan.triplets.len(), 2);
+
+        // A single coefficient other than 1.0 is not a copy.
+        let coefficient = Coefficient::<f32>::create_with_matrix(
+            &[Channel::FrontCenter],
+            &[Channel::FrontLeft],
+            &[vec![0.5]],
+        )
+        .unwrap();
+        assert!(matches!(coefficient.row(0), Row::Sum(triplets) if triplets.len() == 1));
+    }
+
+    #[test]
+    fn test_create_with_normalization() {
+        use float_cmp::approx_eq;
+
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let create = |normalization| {
+            let config = MixerConfig::default().with_normalization(normalization);
+            (
+                Coefficient::<f32>::create(&input_channels, &output_channels, &config).unwrap(),
+                Coefficient::<i16>::create(&input_channels, &output_channels, &config).unwrap(),
+            )
+        };
+        let matrix =
+            Coefficient::<f32>::create(&input_channels, &output_channels, &MixerConfig::default())
+                .unwrap()
+                .normalized_matrix;
+        // Return the factor scaling the default f32 matrix to the given one.
+        let factor = |normalized: &[Vec<f64>]| {
+            let factor = normalized[0][0] / matrix[0][0];
+            for (row, normalized_row) in matrix.iter().zip(normalized.iter()) {
+                for (coef, normalized_coef) in row.iter().zip(normalized_row.iter()) {
+                    assert!(approx_eq!(f64, coef * factor, *normalized_coef));
+                }
+            }
+            factor
+        };
+        let max_row = |normalized: &[Vec<f64>], row_sum: fn(&[f64]) -> f64| {
+            normalized
+                .iter()
+                .map(|row| row_sum(row))
+                .fold(0.0, f64::max)
+        };
+
+        // The matrix is the same for all the sample types.
+        let (f32_coefficient, i16_coefficient) = create(Normalization::PeakSafe);
+        assert_eq!(
+            f32_coefficient.normalized_matrix,
+            i16_coefficient.normalized_matrix
+        );
+        factor(&f32_coefficient.normalized_matrix);
+        let max_sum = max_row(&f32_coefficient.normalized_matrix, |row| row.iter().sum());
+        assert!(approx_eq!(f64, max_sum, 1.0));
+
+        let (f32_coefficient, i16_coefficient) = create(Normalization::PowerPreserving);
+        assert_eq!(
+            f32_coefficient.normalized_matrix,
+            i16_coefficient.normalized_matrix
+        );
+        factor(&f32_coefficient.normalized_matrix);
+        let max_norm = max_row(&f32_coefficient.normalized_matrix, |row| {
+            row.iter().map(|coef| coef * coef).sum::<f64>().sqrt()
+        });
+        assert!(approx_eq!(f64, max_norm, 1.0));
+
+        // The total power of the output channels equals the one of the 6 input channels.
+        let (f32_coefficient, _) = create(Normalization::LoudnessMatched);
+        assert!(factor(&f32_coefficient.normalized_matrix) > 1.0);
+        let power: f64 = f32_coefficient
+            .normalized_matrix
+            .iter()
+            .flatten()
+            .map(|coef| coef * coef)
+            .sum();
+        assert!(approx_eq!(f64, power, 6.0));
+
+        // The matrix for i16 is limited so the mixed value never overflows i32.
+        let (f32_coefficient, i16_coefficient) = create(Normalization::None);
+        assert_eq!(f32_coefficient.normalized_matrix, matrix);
+        assert!(factor(&i16_coefficient.normalized_matrix) < 1.0);
+        let max_absolute_sum = max_row(&i16_coefficient.normalized_matrix, |row| {
+            row.iter().map(|coef| coef.abs()).sum()
+        });
+        assert!(approx_eq!(
+            f64,
+            max_absolute_sum,
+            i16::max_coefficients_sum()
+        ));
+        assert_eq!(
+            i16_coefficient.would_overflow_from_coefficient_value,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_create_with_height_channels() {
+        use float_cmp::approx_eq;
+        use std::f64::consts::FRAC_1_SQRT_2;
+
+        let height_mix_level = 0.5;
+        let config = MixerConfig::default().with_height_mix_level(height_mix_level);
+        let coefficient_of = |input_channels: &[Channel],
+                              output_channels: &[Channel],
+                              input: Channel,
+                              output: Channel| {
+            let coefficient =
+                Coefficient::<f32>::create(input_channels, output_channels, &config).unwrap();
+            let i = output_channels.iter().position(|c| c == &output).unwrap();
+            let j = input_channels.iter().position(|c| c == &input).unwrap();
+            coefficient.matrix()[i][j]
+        };
+
+        // 7.1.4 to 5.1: top-front channels go to fronts and top-back channels go to sides.
+        let input_channels = ChannelLayout::surround_7_1_4();
+        let output_channels = ChannelLayout::surround_5_1();
+        for (input, output, expected) in &[
+            (Channel::TopFrontLeft, Channel::FrontLeft, height_mix_level),
+            (
+                Channel::TopFrontRight,
+                Channel::FrontRight,
+                height_mix_level,
+            ),
+            (Channel::TopFrontLeft, Channel::FrontRight, 0.0),
+            (Channel::TopBackLeft, Channel::SideLeft, height_mix_level),
+            (Channel::TopBackRight, Channel::SideRight, height_mix_level),
+        ] {
+            let coef = coefficient_of(&input_channels, &output_channels, *input, *output);
+            assert!(approx_eq!(f32, coef, *expected as f32));
+        }
+
+        // 7.1.4 to stereo: top-back channels go to fronts with the surround level as well.
+        let output_channels = ChannelLayout::stereo();
+        for (input, output, expected) in &[
+            (Channel::TopFrontLeft, Channel::FrontLeft, height_mix_level),
+            (
+                Channel::TopBackRight,
+                Channel::FrontRight,
+                height_mix_level * FRAC_1_SQRT_2,
+            ),
+        ] {
+            let coef = coefficient_of(&input_channels, &output_channels, *input, *output);
+            assert!(approx_eq!(f32, coef, *expected as f32));
+        }
+
+        // Top-center is spread to all the ear-level stereo channels with the height level.
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight, Channel::TopCenter];
+        let output_channels = ChannelLayout::quad();
+        for output in output_channels.iter() {
+            let coef = coefficient_of(
+                &input_channels,
+                &output_channels,
+                Channel::TopCenter,
+                *output,
+            );
+            assert!(approx_eq!(f32, coef, (height_mix_level / 2.0) as f32));
+        }
+
+        // or spread to all the top channels evenly if output has them.
+        let output_channels = ChannelLayout::surround_5_1_2_back();
+        for (output, expected) in &[
+            (Channel::FrontLeft, 0.0),
+            (Channel::TopFrontLeft, FRAC_1_SQRT_2),
+            (Channel::TopFrontRight, FRAC_1_SQRT_2),
+        ] {
+            let coef = coefficient_of(
+                &input_channels,
+                &output_channels,
+                Channel::TopCenter,
+                *output,
+            );
+            assert!(approx_eq!(f32, coef, *expected as f32));
+        }
+    }
+
+    #[test]
+    fn test_create_with_matrix() {
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight, Channel::Silence];
+        let output_channels = [Channel::FrontCenter, Channel::Silence];
+        let matrix = vec![vec![0.75, 0.75, 1.0], vec![1.0, 1.0, 1.0]];
+
+        // f32 coefficients are used as they are, except the ones for Silence channels.
+        let coefficient =
+            Coefficient::<f32>::create_with_matrix(&input_channels, &output_channels, &matrix)
+                .unwrap();
+        assert_eq!(
+            coefficient.matrix(),
+            vec![vec![0.75, 0.75, 0.0], vec![0.0, 0.0, 0.0]]
+        );
+
+        // i16 coefficients are normalized so the row sum is not greater than 1.
+        let coefficient =
+            Coefficient::<i16>::create_with_matrix(&input_channels, &output_channels, &matrix)
+                .unwrap();
+        assert_eq!(
+            coefficient.matrix(),
+            vec![vec![1 << 14, 1 << 14, 0], vec![0, 0, 0]]
+        );
+        assert_eq!(
+            coefficient.would_overflow_from_coefficient_value(),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_create_with_invalid_matrix() {
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let output_channels = [Channel::FrontCenter];
+
+        let matrix = vec![vec![1.0, 1.0], vec![1.0, 1.0]];
+        assert_eq!(
+            Coefficient::<f32>::create_with_matrix(&input_channels, &output_channels, &matrix)
+                .err(),
+            Some(MixerError::MatrixSizeMismatch {
+                side: Side::Output,
+                expected: 1,
+                actual: 2,
+            })
+        );
+
+        let matrix = vec![vec![1.0, 1.0, 1.0]];
+        assert_eq!(
+            Coefficient::<f32>::create_with_matrix(&input_channels, &output_channels, &matrix)
+                .err(),
+            Some(MixerError::MatrixSizeMismatch {
+                side: Side::Input,
+                expected: 2,
+                actual: 3,
+            })
+        );
+
+        let matrix = vec![vec![1.0, f64::NAN]];
+        assert_eq!(
+            Coefficient::<i16>::create_with_matrix(&input_channels, &output_channels, &matrix)
+                .err(),
+            Some(MixerError::NonFiniteCoefficient { row: 0, column: 1 })
         );
     }
 
@@ -732,7 +2404,7 @@ mod test {
             vec![4.0_f64, 6.0_f64, 10.0_f64],
         ];
 
-        let mut max_row_sum: f64 = std::f64::MIN;
+        let mut max_row_sum: f64 = f64::MIN;
         for row in &m {
             max_row_sum = max_row_sum.max(row.iter().sum());
         }
@@ -746,11 +2418,46 @@ mod test {
         let smaller_max = max_row_sum - 0.5_f64;
         assert!(smaller_max > 0.0_f64);
         let n = Coefficient::<f32>::normalize(smaller_max, m);
-        let mut max_row_sum: f64 = std::f64::MIN;
+        let mut max_row_sum: f64 = f64::MIN;
         for row in &n {
             max_row_sum = max_row_sum.max(row.iter().sum());
             assert!(row.iter().sum::<f64>() <= smaller_max);
         }
         assert!(approx_eq!(f64, smaller_max, max_row_sum));
     }
+
+    #[test]
+    fn test_sample_conversion() {
+        // The full scales of the integer types are mapped to each other.
+        assert_eq!(i32::from_sample(i16::MIN), i32::MIN);
+        assert_eq!(i32::from_sample(0x4000_i16), 0x4000_0000);
+        assert_eq!(i16::from_sample(0x4000_0000_i32), 0x4000);
+        assert_eq!(i16::from_sample(i32::MAX), i16::MAX);
+        assert_eq!(u8::from_sample(i16::MIN), 0);
+        assert_eq!(u8::from_sample(0_i16), 128);
+        assert_eq!(i16::from_sample(255_u8), 0x7F00);
+        assert_eq!(I24::from_sample(i16::MIN), I24::new(I24::MIN));
+        assert_eq!(i32::from_sample(I24::new(I24::MAX)), I24::MAX << 8);
+        assert_eq!(
+            I24In32::from_sample(I24::new(-0x12_3456)).to_container(),
+            -0x12_3456
+        );
+
+        // The integer samples are mapped to [-1.0, 1.0) in float.
+        assert_eq!(f32::from_sample(i16::MIN), -1.0);
+        assert_eq!(f64::from_sample(0x4000_i16), 0.5);
+        assert_eq!(f32::from_sample(0_u8), -1.0);
+        assert_eq!(f64::from_sample(I24::new(-0x40_0000)), -0.5);
+        assert_eq!(f32::from_sample(0.25_f64), 0.25);
+
+        // The float samples out of range are clipped.
+        assert_eq!(i16::from_sample(1.0_f32), i16::MAX);
+        assert_eq!(i16::from_sample(-2.0_f32), i16::MIN);
+        assert_eq!(i32::from_sample(1.5_f64), i32::MAX);
+        assert_eq!(u8::from_sample(-1.5_f64), 0);
+        assert_eq!(u8::from_sample(1.0_f64), 255);
+        assert_eq!(I24::from_sample(1.0_f32), I24::new(I24::MAX));
+        assert_eq!(I24In32::from_sample(-1.0_f32), I24In32::new(I24::MIN));
+        assert_eq!(i16::from_sample(f32::NAN), 0);
+    }
 }
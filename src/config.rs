@@ -0,0 +1,186 @@
+use crate::dither::{Dither, DEFAULT_DITHER_SEED};
+use crate::protection::OutputProtection;
+use std::f64::consts::FRAC_1_SQRT_2;
+
+// The levels used to generate the mixing matrix. A level is a linear gain applied to the
+// channel data when the channel is mixed into other channels that are present in the output.
+// They don't change the data of the channels that are present in both input and output.
+//
+// The default levels are the ones used by libswresample and libcubeb:
+// center and surround levels are -3 dB, and LFE level is 0 dB. The height level, which
+// libswresample and libcubeb don't have, is -3 dB as well.
+//
+// Common levels in dB can be converted by gain_from_db. For example,
+//
+// MixerConfig::default()
+//     .with_center_mix_level(gain_from_db(-4.5))
+//     .with_lfe_mix_level(0.0)
+//
+// mixes the center channel at -4.5 dB and drops the LFE channel when downmixing.
+//
+// The config also selects the normalization of the mixing matrix, the dither and noise shaping
+// applied when the mixed data is written to integer samples, and the output protection applied
+// when it's written to float samples. They are disabled by default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixerConfig {
+    center_mix_level: f64,
+    surround_mix_level: f64,
+    lfe_mix_level: f64,
+    height_mix_level: f64,
+    normalization: Option<Normalization>,
+    dither: Dither,
+    noise_shaping: bool,
+    dither_seed: u64,
+    output_protection: OutputProtection,
+}
+
+impl Default for MixerConfig {
+    fn default() -> Self {
+        Self {
+            center_mix_level: FRAC_1_SQRT_2,
+            surround_mix_level: FRAC_1_SQRT_2,
+            lfe_mix_level: 1.0,
+            height_mix_level: FRAC_1_SQRT_2,
+            normalization: None,
+            dither: Dither::None,
+            noise_shaping: false,
+            dither_seed: DEFAULT_DITHER_SEED,
+            output_protection: OutputProtection::None,
+        }
+    }
+}
+
+impl MixerConfig {
+    // The level for mixing the front-center channel into the front-stereo channels.
+    pub fn with_center_mix_level(mut self, level: f64) -> Self {
+        self.center_mix_level = check_level(level);
+        self
+    }
+
+    // The level for mixing the back and side channels into the front channels.
+    pub fn with_surround_mix_level(mut self, level: f64) -> Self {
+        self.surround_mix_level = check_level(level);
+        self
+    }
+
+    // The level for mixing the low-frequency channel into the front channels.
+    // Set it to 0 to drop the low-frequency channel when output has no low-frequency channel.
+    pub fn with_lfe_mix_level(mut self, level: f64) -> Self {
+        self.lfe_mix_level = check_level(level);
+        self
+    }
+
+    // The level for mixing the top channels into the channels at ear level. It's applied on top
+    // of the surround level when the top-back channels are mixed into the front channels.
+    pub fn with_height_mix_level(mut self, level: f64) -> Self {
+        self.height_mix_level = check_level(level);
+        self
+    }
+
+    // The normalization applied to the mixing matrix for all the sample types. By default, the
+    // matrix is normalized by PeakSafe for the integer samples, and it's not normalized for the
+    // float samples.
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    // The dither added to the mixed data before it's quantized to the integer output samples.
+    // It has no effect on the float output samples.
+    pub fn with_dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    // Feed the quantization error of each integer output sample back to the next sample in the
+    // same channel, which moves the quantization noise to the high frequencies.
+    pub fn with_noise_shaping(mut self, noise_shaping: bool) -> Self {
+        self.noise_shaping = noise_shaping;
+        self
+    }
+
+    // The seed of the random number generator for the dither. The mixers created with the
+    // same seed generate the same output from the same input.
+    pub fn with_dither_seed(mut self, seed: u64) -> Self {
+        self.dither_seed = seed;
+        self
+    }
+
+    // The processing that keeps the float output samples in range. It has no effect on the
+    // integer output samples, which are always clipped.
+    pub fn with_output_protection(mut self, protection: OutputProtection) -> Self {
+        self.output_protection = protection;
+        self
+    }
+
+    pub fn center_mix_level(&self) -> f64 {
+        self.center_mix_level
+    }
+
+    pub fn surround_mix_level(&self) -> f64 {
+        self.surround_mix_level
+    }
+
+    pub fn lfe_mix_level(&self) -> f64 {
+        self.lfe_mix_level
+    }
+
+    pub fn height_mix_level(&self) -> f64 {
+        self.height_mix_level
+    }
+
+    // Return None if the normalization is not specified, so the default one of the sample type
+    // is used.
+    pub fn normalization(&self) -> Option<Normalization> {
+        self.normalization
+    }
+
+    pub fn dither(&self) -> Dither {
+        self.dither
+    }
+
+    pub fn noise_shaping(&self) -> bool {
+        self.noise_shaping
+    }
+
+    pub fn dither_seed(&self) -> u64 {
+        self.dither_seed
+    }
+
+    pub fn output_protection(&self) -> OutputProtection {
+        self.output_protection
+    }
+}
+
+// The ways to scale the mixing matrix. The whole matrix is scaled by the same factor, so the
+// balance between the channels is kept. The matrix for the integer samples is scaled down
+// further if the mixed data would overflow the intermediate integer values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    // The matrix is used as it is.
+    None,
+    // The matrix is scaled down so the sum of every row is not greater than 1, so the mixed
+    // data of the in-phase full-scale input never exceeds the full scale.
+    PeakSafe,
+    // The matrix is scaled down so the L2 norm of every row is not greater than 1, so the power
+    // of every output channel never exceeds the power of an input channel when the input
+    // channels are uncorrelated and equally loud.
+    PowerPreserving,
+    // The matrix is scaled so the total power of the output channels equals the total power of
+    // the non-silence input channels, when the input channels are uncorrelated and equally loud.
+    // The matrix may be scaled up by this.
+    LoudnessMatched,
+}
+
+// Convert a level in dB to a linear gain, e.g., -6 dB is about 0.5.
+pub fn gain_from_db(db: f64) -> f64 {
+    10_f64.powf(db / 20.0)
+}
+
+fn check_level(level: f64) -> f64 {
+    assert!(
+        level.is_finite() && level >= 0.0,
+        "mix level must be a finite non-negative number."
+    );
+    level
+}
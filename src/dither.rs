@@ -0,0 +1,133 @@
+// The noise added to the mixed data before it's quantized to the integer output samples, to
+// turn the truncation distortion into a constant noise floor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    // No noise is added. The mixed data is rounded to the nearest integer.
+    None,
+    // Rectangular probability density function noise in [-0.5, 0.5) LSB.
+    Rectangular,
+    // Triangular probability density function noise in (-1, 1) LSB, i.e., TPDF dither. It's the
+    // sum of two rectangular noises, which makes the noise power independent of the signal.
+    Triangular,
+}
+
+// The seed used when MixerConfig doesn't specify one, so the dithered output is reproducible.
+pub const DEFAULT_DITHER_SEED: u64 = 0x853C_49E6_748F_EA9B;
+
+// The state of the dither and noise shaping for all the output channels of a mixer.
+#[derive(Debug)]
+pub struct Ditherer {
+    dither: Dither,
+    random: Random,
+    // The quantization errors of the previous samples in all the output channels, in LSB, or
+    // None if the noise shaping is disabled.
+    errors: Option<Vec<f64>>,
+}
+
+impl Ditherer {
+    // Return None if neither dither nor noise shaping is enabled.
+    pub fn new(dither: Dither, noise_shaping: bool, seed: u64, channels: usize) -> Option<Self> {
+        if dither == Dither::None && !noise_shaping {
+            return None;
+        }
+        Some(Self {
+            dither,
+            random: Random::new(seed),
+            errors: noise_shaping.then(|| vec![0.0; channels]),
+        })
+    }
+
+    // Return the offset in LSB that should be added to the next sample of the given channel
+    // before it's quantized.
+    pub fn offset(&mut self, channel: usize) -> f64 {
+        let noise = match self.dither {
+            Dither::None => 0.0,
+            Dither::Rectangular => self.random.next_f64() - 0.5,
+            Dither::Triangular => self.random.next_f64() - self.random.next_f64(),
+        };
+        match &self.errors {
+            Some(errors) => noise - errors[channel],
+            None => noise,
+        }
+    }
+
+    // Record the difference in LSB between the quantized sample of the given channel and the
+    // mixed value before the offset is added. When noise shaping is enabled, the quantization
+    // error, i.e., the difference between the quantized sample and the value with the previous
+    // error subtracted, is fed back to the next sample of the channel, so the noise is shaped by
+    // a first-order high-pass filter, (1 - z^-1), and moved away from the frequencies where the
+    // hearing is sensitive. The error is bounded so clipping can't make it unstable.
+    pub fn feedback(&mut self, channel: usize, difference: f64) {
+        if let Some(errors) = &mut self.errors {
+            errors[channel] = (difference + errors[channel]).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+// A xorshift64* pseudo-random number generator. It's good enough for the dither noise, and it
+// generates the same sequence for the same seed on all platforms.
+#[derive(Debug)]
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        // The state of xorshift must not be zero.
+        Self(if seed == 0 { DEFAULT_DITHER_SEED } else { seed })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Return a number uniformly distributed in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let mut a = Random::new(1);
+        let mut b = Random::new(1);
+        let mut c = Random::new(2);
+        for _ in 0..1000 {
+            let value = a.next_f64();
+            assert!((0.0..1.0).contains(&value));
+            assert_eq!(value, b.next_f64());
+            assert_ne!(value, c.next_f64());
+        }
+    }
+
+    #[test]
+    fn test_ditherer() {
+        assert!(Ditherer::new(Dither::None, false, 1, 2).is_none());
+
+        let mut ditherer = Ditherer::new(Dither::Rectangular, false, 1, 2).unwrap();
+        let offsets: Vec<f64> = (0..1000).map(|i| ditherer.offset(i % 2)).collect();
+        assert!(offsets.iter().all(|offset| (-0.5..0.5).contains(offset)));
+
+        let mut ditherer = Ditherer::new(Dither::Triangular, false, 1, 2).unwrap();
+        let offsets: Vec<f64> = (0..1000).map(|i| ditherer.offset(i % 2)).collect();
+        assert!(offsets.iter().all(|offset| offset.abs() < 1.0));
+        // The triangular noise is concentrated around zero.
+        let mean_square = offsets.iter().map(|offset| offset * offset).sum::<f64>() / 1000.0;
+        assert!((mean_square - 1.0 / 6.0).abs() < 0.02);
+
+        // The errors are fed back to the same channel only.
+        let mut ditherer = Ditherer::new(Dither::None, true, 1, 2).unwrap();
+        assert_eq!(ditherer.offset(0), 0.0);
+        ditherer.feedback(0, 0.25);
+        ditherer.feedback(1, 3.0);
+        assert_eq!(ditherer.offset(0), -0.25);
+        assert_eq!(ditherer.offset(1), -1.0);
+        ditherer.feedback(0, 0.5);
+        assert_eq!(ditherer.offset(0), -0.75);
+    }
+}
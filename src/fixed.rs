@@ -0,0 +1,228 @@
+use crate::channel::Channel;
+use crate::coefficient::{Coefficient, MixerError, MixingCoefficient};
+use crate::config::MixerConfig;
+use crate::sample::Sample;
+use crate::stream::State;
+use crate::{check_frames, interleaved_frames, Side};
+use std::convert::TryInto;
+
+type Coef<T> = <<T as Sample>::Native as MixingCoefficient>::Coef;
+
+// A mixer whose numbers of the input and output channels are known at compile time. The mixing
+// matrix is held in an OUTxIN array rather than on the heap, and the frames are mixed as arrays,
+// so the loops have constant bounds and the compiler can fully unroll them for the common cases
+// like 6 -> 2 and 1 -> 2. The matrix is the same as the one of Mixer<T> with the same channels
+// and config, and the mixed data is the same as the one mixed by Mixer<T>. Like Mixer, it keeps
+// no state, so neither dither nor output protection is applied.
+#[derive(Debug)]
+pub struct FixedMixer<T, const IN: usize, const OUT: usize>
+where
+    T: Sample,
+{
+    input_channels: [Channel; IN],
+    output_channels: [Channel; OUT],
+    // The coefficient for mixing input-channel j to output-channel i is matrix[i][j].
+    matrix: [[Coef<T>; IN]; OUT],
+    normalized_matrix: Vec<Vec<f64>>,
+    would_overflow: Option<bool>,
+}
+
+impl<T, const IN: usize, const OUT: usize> FixedMixer<T, IN, OUT>
+where
+    T: Sample,
+{
+    // Panic if the input or output channels are invalid. Use try_new to handle the error instead.
+    pub fn new(input_channels: &[Channel; IN], output_channels: &[Channel; OUT]) -> Self {
+        Self::try_new(input_channels, output_channels).expect("Invalid channel layout")
+    }
+
+    // Return an error if there are duplicate non-silence channels in input or output channels.
+    pub fn try_new(
+        input_channels: &[Channel; IN],
+        output_channels: &[Channel; OUT],
+    ) -> Result<Self, MixerError> {
+        Self::with_config(input_channels, output_channels, &MixerConfig::default())
+    }
+
+    // Create a mixer whose mixing matrix is generated with the levels and normalization in the
+    // given config. Return an error if the config enables the dither or output protection for
+    // the output samples. See Mixer::with_config.
+    pub fn with_config(
+        input_channels: &[Channel; IN],
+        output_channels: &[Channel; OUT],
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let coefficient = Coefficient::create(input_channels, output_channels, config)?;
+        let would_overflow = coefficient.would_overflow_from_coefficient_value();
+        if State::new(config, OUT, would_overflow)?.is_some() {
+            return Err(MixerError::StatefulConfig);
+        }
+        Ok(Self::with_coefficient(coefficient))
+    }
+
+    // Create a mixer with the given OUTxIN mixing matrix. See Mixer::from_matrix.
+    pub fn from_matrix(
+        input_channels: &[Channel; IN],
+        output_channels: &[Channel; OUT],
+        matrix: &[[f64; IN]; OUT],
+    ) -> Result<Self, MixerError> {
+        let matrix: Vec<Vec<f64>> = matrix.iter().map(|row| row.to_vec()).collect();
+        let coefficient =
+            Coefficient::create_with_matrix(input_channels, output_channels, &matrix)?;
+        Ok(Self::with_coefficient(coefficient))
+    }
+
+    fn with_coefficient(coefficient: Coefficient<T::Native>) -> Self {
+        let mut matrix = [[Coef::<T>::default(); IN]; OUT];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, coef) in row.iter_mut().enumerate() {
+                *coef = coefficient.get(j, i);
+            }
+        }
+        let mut input_channels = [Channel::Silence; IN];
+        input_channels.copy_from_slice(coefficient.input_channels());
+        let mut output_channels = [Channel::Silence; OUT];
+        output_channels.copy_from_slice(coefficient.output_channels());
+        Self {
+            input_channels,
+            output_channels,
+            matrix,
+            normalized_matrix: coefficient.normalized_matrix().to_vec(),
+            would_overflow: coefficient.would_overflow_from_coefficient_value(),
+        }
+    }
+
+    // Mix a frame of the input channels to a frame of the output channels.
+    pub fn mix(&self, input: &[T; IN], output: &mut [T; OUT]) {
+        let mut converted = [Coef::<T>::default(); IN];
+        for (converted, data) in converted.iter_mut().zip(input.iter()) {
+            *converted = T::Native::to_coefficient_value(T::to_native(*data));
+        }
+        for (data, row) in output.iter_mut().zip(&self.matrix) {
+            // Coef must implement Default that returns a zero value from default().
+            let mut value = Coef::<T>::default();
+            for (coef, data) in row.iter().zip(&converted) {
+                value += *coef * *data;
+            }
+            *data = T::from_native(T::Native::from_coefficient_value(
+                value,
+                self.would_overflow,
+            ));
+        }
+    }
+
+    // Mix all the interleaved frames in input buffer to output buffer and return the number of
+    // mixed frames. See Mixer::mix_interleaved.
+    pub fn mix_interleaved(&self, input_buffer: &[T], output_buffer: &mut [T]) -> usize {
+        let frames = interleaved_frames(input_buffer, IN, Side::Input);
+        check_frames(frames, interleaved_frames(output_buffer, OUT, Side::Output));
+        for (input, output) in input_buffer
+            .chunks_exact(IN)
+            .zip(output_buffer.chunks_exact_mut(OUT))
+        {
+            let input: &[T; IN] = input.try_into().expect("frame must have IN samples");
+            let output: &mut [T; OUT] = output.try_into().expect("frame must have OUT samples");
+            self.mix(input, output);
+        }
+        frames
+    }
+
+    pub fn input_channels(&self) -> &[Channel; IN] {
+        &self.input_channels
+    }
+
+    pub fn output_channels(&self) -> &[Channel; OUT] {
+        &self.output_channels
+    }
+
+    // Return the OUTxIN mixing matrix. See Mixer::matrix.
+    pub fn matrix(&self) -> Vec<Vec<f64>> {
+        self.normalized_matrix.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::ChannelLayout;
+    use crate::Mixer;
+
+    fn test_same_as_mixer<T, const IN: usize, const OUT: usize>(
+        input_channels: &[Channel; IN],
+        output_channels: &[Channel; OUT],
+        sample: fn(usize) -> T,
+    ) where
+        T: Sample + Default + PartialEq,
+    {
+        let fixed = FixedMixer::<T, IN, OUT>::new(input_channels, output_channels);
+        let mixer = Mixer::<T>::new(input_channels, output_channels);
+        assert_eq!(fixed.matrix(), mixer.matrix());
+
+        let frames = 32;
+        let input: Vec<T> = (0..frames * IN).map(sample).collect();
+        let mut output = vec![T::default(); frames * OUT];
+        assert_eq!(fixed.mix_interleaved(&input, &mut output), frames);
+        let mut expected = vec![T::default(); frames * OUT];
+        mixer.mix_interleaved(&input, &mut expected);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_fixed_mixer() {
+        let surround_5_1 = ChannelLayout::surround_5_1();
+        let surround_5_1: &[Channel; 6] = surround_5_1.channels().try_into().unwrap();
+        let stereo = [Channel::FrontLeft, Channel::FrontRight];
+        let mono = [Channel::FrontCenter];
+        let sample_f32 = |i: usize| ((i * 37) % 101) as f32 / 50.0 - 1.0;
+        let sample_i16 = |i: usize| (i * 4099) as i16;
+        test_same_as_mixer(surround_5_1, &stereo, sample_f32);
+        test_same_as_mixer(surround_5_1, &stereo, sample_i16);
+        test_same_as_mixer(&mono, &stereo, sample_f32);
+        test_same_as_mixer(&mono, &stereo, sample_i16);
+        test_same_as_mixer(&stereo, &mono, |i| (i * 13) as u8);
+        test_same_as_mixer(&stereo, &[Channel::FrontLeft, Channel::Silence], |i| {
+            (i * 65537) as i32
+        });
+
+        let fixed = FixedMixer::<f32, 2, 1>::from_matrix(&stereo, &mono, &[[0.25, 0.5]]).unwrap();
+        assert_eq!(fixed.input_channels(), &stereo);
+        assert_eq!(fixed.output_channels(), &mono);
+        let mut output = [0.0];
+        fixed.mix(&[1.0, 0.5], &mut output);
+        assert_eq!(output, [0.5]);
+
+        assert_eq!(
+            FixedMixer::<f32, 2, 1>::try_new(&[Channel::FrontLeft; 2], &mono).unwrap_err(),
+            MixerError::DuplicateNonSilenceChannel {
+                side: Side::Input,
+                channel: Channel::FrontLeft,
+            }
+        );
+
+        // The stateful processing in the config is rejected rather than ignored.
+        let config = MixerConfig::default().with_dither(crate::Dither::Triangular);
+        assert_eq!(
+            FixedMixer::<i16, 2, 1>::with_config(&stereo, &mono, &config).unwrap_err(),
+            MixerError::StatefulConfig
+        );
+        assert!(FixedMixer::<f32, 2, 1>::with_config(&stereo, &mono, &config).is_ok());
+        let config =
+            MixerConfig::default().with_output_protection(crate::OutputProtection::SoftClip);
+        assert_eq!(
+            FixedMixer::<f32, 2, 1>::with_config(&stereo, &mono, &config).unwrap_err(),
+            MixerError::StatefulConfig
+        );
+        assert!(FixedMixer::<i16, 2, 1>::with_config(&stereo, &mono, &config).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mix_interleaved_with_different_frames() {
+        let fixed = FixedMixer::<f32, 1, 2>::new(
+            &[Channel::FrontCenter],
+            &[Channel::FrontLeft, Channel::FrontRight],
+        );
+        let mut output = [0.0; 4];
+        let _ = fixed.mix_interleaved(&[0.0; 3], &mut output);
+    }
+}
@@ -1,41 +1,126 @@
 #[macro_use]
 extern crate bitflags;
 
+mod bus;
 mod channel;
 mod coefficient;
+mod config;
+mod dither;
+mod fixed;
+mod protection;
+mod ramp;
+mod sample;
+mod simd;
+mod stream;
 
-// Export Channel outside.
-pub use channel::Channel;
-use coefficient::{Coefficient, MixingCoefficient};
-
-use std::default::Default;
-use std::fmt::Debug;
-use std::ops::{AddAssign, Mul};
-
-// A mixer mixing M-channel input data to N-channel output data.
-// T::Coef is an associated type defined in MixingCoefficient, which indicates the type of the
-// mixing coefficient that would be used for type T. When T is f32, the T::Coef is f32. When T
-// is i16, the T::Coef is i32. When mixing data, a temporary variable with type T::Coef would be
-// created to hold the mixing result. Since the type of input and output audio data is T,
-// the methods provided from MixingCoefficient trait would be used to convert the value between
-// type T and T::Coef.
+// Export Channel and ChannelLayout outside.
+pub use channel::{Channel, ChannelLayout};
+
+pub use bus::{MixBus, SourceId};
+use coefficient::{Coefficient, MixingCoefficient, Row};
+pub use coefficient::{MixerError, Side};
+pub use config::{gain_from_db, MixerConfig, Normalization};
+pub use dither::Dither;
+use dither::Ditherer;
+pub use fixed::FixedMixer;
+pub use protection::OutputProtection;
+pub use ramp::{RampCurve, RampingMixer};
+pub use sample::{I24In32, Sample, I24};
+use simd::Kernel;
+// The kernels are exported only for the benchmarks comparing the instruction sets.
+#[doc(hidden)]
+pub use simd::{F32Kernel, I16Kernel, Level};
+pub use stream::MixerStream;
+use stream::State;
+
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+// A mixer mixing M-channel input data in type I to N-channel output data in type O. O is the
+// same as I by default.
+// The mixing is done in O::Native, the natively supported sample type of O (see Sample), and
+// its Coef is an associated type defined in MixingCoefficient, which indicates the type of the
+// mixing coefficient that would be used. When the native type is f32 or f64, the Coef is the
+// same as it. When the native type is i16 or u8, the Coef is i32. When the native type is i32,
+// I24 or I24In32, the Coef is i64. When mixing data, a temporary variable with type Coef would
+// be created to hold the mixing result. When I::Native is the same as O::Native, the methods
+// provided from MixingCoefficient trait would be used to convert the value between O::Native and
+// its Coef. Otherwise, each input sample is converted to f64 once per frame and mixed in f64, and
+// only the mixed value is converted to the Coef. The integer samples are scaled so their full
+// scale matches the [-1.0, 1.0) range of the float samples, and the output values out of range
+// are clipped.
 #[derive(Debug)]
-pub struct Mixer<T>
+pub struct Mixer<I, O = I>
 where
-    T: Copy + Debug + MixingCoefficient,
-    T::Coef: AddAssign + Copy + Debug + Default + Mul<T::Coef, Output = T::Coef>,
+    I: Sample,
+    O: Sample,
 {
-    coefficient: Coefficient<T>,
+    coefficient: Coefficient<O::Native>,
+    // The input channel copied to each output channel, or None for the silent ones, when the
+    // matrix only reorders the channels. The data is copied rather than mixed then, which is
+    // bit-exact. It's not used when the data is processed with the state of a MixerStream.
+    permutation: Option<Vec<Option<usize>>>,
+    // The SIMD kernel mixing the interleaved frames when both I and O are f32 or i16. It's not
+    // used with the state of a MixerStream either.
+    kernel: Option<Kernel>,
+    input_type: PhantomData<I>,
 }
 
-impl<T> Mixer<T>
+impl<I, O> Mixer<I, O>
 where
-    T: Copy + Debug + MixingCoefficient,
-    T::Coef: AddAssign + Copy + Debug + Default + Mul<T::Coef, Output = T::Coef>,
+    I: Sample,
+    O: Sample,
 {
+    // Panic if the input or output channels are invalid. Use try_new to handle the error instead.
     pub fn new(input_channels: &[Channel], output_channels: &[Channel]) -> Self {
+        Self::try_new(input_channels, output_channels).expect("Invalid channel layout")
+    }
+
+    // Return an error if there are duplicate non-silence channels in input or output channels.
+    pub fn try_new(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+    ) -> Result<Self, MixerError> {
+        Self::with_config(input_channels, output_channels, &MixerConfig::default())
+    }
+
+    // Create a mixer whose mixing matrix is generated with the levels in the given config.
+    // Return an error if the config enables the dither or noise shaping for the integer output
+    // samples, or the output protection for the float output samples, since their state depends
+    // on the previous samples. Use MixerStream instead then.
+    pub fn with_config(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let coefficient = Coefficient::create(input_channels, output_channels, config)?;
+        let would_overflow = coefficient.would_overflow_from_coefficient_value();
+        if State::new(config, output_channels.len(), would_overflow)?.is_some() {
+            return Err(MixerError::StatefulConfig);
+        }
+        Ok(Self::with_coefficient(coefficient))
+    }
+
+    // Create a mixer with the given NxM mixing matrix rather than generating one from the input
+    // and output channels, where matrix[i][j] is the coefficient for mixing input-channel j to
+    // output-channel i. The coefficients for Silence channels are ignored. The matrix may be
+    // normalized to avoid overflow when O::Native is an integer type.
+    pub fn from_matrix(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        matrix: &[Vec<f64>],
+    ) -> Result<Self, MixerError> {
+        let coefficient = Coefficient::create_with_matrix(input_channels, output_channels, matrix)?;
+        Ok(Self::with_coefficient(coefficient))
+    }
+
+    fn with_coefficient(coefficient: Coefficient<O::Native>) -> Self {
+        let (permutation, kernel) = Self::create_fast_paths(&coefficient);
         Self {
-            coefficient: Coefficient::create(input_channels, output_channels),
+            coefficient,
+            permutation,
+            kernel,
+            input_type: PhantomData,
         }
     }
 
@@ -45,7 +130,111 @@ where
     // output_data(i) = Σ coefficient(j, i) * input_data(j), for all j in [0, M),
     // where i is in [0, N) and coefficient is a function returning mixing coefficient from
     // input channel j to output channel i.
-    pub fn mix(&self, input_buffer: &[T], output_buffer: &mut [T]) {
+    pub fn mix(&self, input_buffer: &[I], output_buffer: &mut [O]) {
+        self.mix_with_state(None, input_buffer, output_buffer);
+    }
+
+    // Mix all the interleaved frames in input buffer to output buffer and return the number of
+    // mixed frames. The input and output buffer must contain the same number of frames, that is,
+    // input_buffer.len() / M == output_buffer.len() / N, and the buffer sizes are validated
+    // only once, rather than once per frame.
+    pub fn mix_interleaved(&self, input_buffer: &[I], output_buffer: &mut [O]) -> usize {
+        self.mix_interleaved_with_state(None, input_buffer, output_buffer)
+    }
+
+    // Mix all the interleaved frames in input buffer, multiplied by the gain, and add them to the
+    // data in output buffer, rather than overwriting it, so multiple streams can be mixed into
+    // the same output buffer. Return the number of mixed frames. The buffers are the same as the
+    // ones of mix_interleaved. The integer sums are clipped to the range of the output samples.
+    // The sums are intermediate data, so neither dither nor output protection is applied.
+    pub fn mix_add(&self, input_buffer: &[I], output_buffer: &mut [O], gain: f64) -> usize {
+        assert!(gain.is_finite(), "gain must be a finite number.");
+        let input_channels = self.input_channels().len();
+        let output_channels = self.output_channels().len();
+        let frames = interleaved_frames(input_buffer, input_channels, Side::Input);
+        check_frames(
+            frames,
+            interleaved_frames(output_buffer, output_channels, Side::Output),
+        );
+        let mut frame = InputFrame::<I, O>::new(input_channels);
+        for (input, output) in input_buffer
+            .chunks_exact(input_channels)
+            .zip(output_buffer.chunks_exact_mut(output_channels))
+        {
+            frame.read(|j| input[j]);
+            for (i, data) in output.iter_mut().enumerate() {
+                let value = frame.mix(&self.coefficient, i, |j| input[j]);
+                *data = O::from_native(O::Native::add_coefficient_value(
+                    O::to_native(*data),
+                    value,
+                    gain,
+                ));
+            }
+        }
+        frames
+    }
+
+    // Mix the interleaved frames like mix_add, but add the mixed data to the f64 samples in the
+    // accumulator, in the same full scale as MixingCoefficient::to_f64_sample. The sums are
+    // neither rounded nor clipped, so they don't depend on the order the streams are added in.
+    fn mix_add_f64(&self, input_buffer: &[I], accumulator: &mut [f64], gain: f64) -> usize {
+        assert!(gain.is_finite(), "gain must be a finite number.");
+        let input_channels = self.input_channels().len();
+        let output_channels = self.output_channels().len();
+        let frames = interleaved_frames(input_buffer, input_channels, Side::Input);
+        check_frames(
+            frames,
+            interleaved_frames(accumulator, output_channels, Side::Output),
+        );
+        let mut frame = InputFrame::<I, O>::with_conversion(input_channels, true);
+        for (input, output) in input_buffer
+            .chunks_exact(input_channels)
+            .zip(accumulator.chunks_exact_mut(output_channels))
+        {
+            frame.read(|j| input[j]);
+            for (i, data) in output.iter_mut().enumerate() {
+                *data += gain * frame.mix_f64(&self.coefficient, i);
+            }
+        }
+        frames
+    }
+
+    // Mix the planar input buffers to the planar output buffers and return the number of mixed
+    // frames. The data of input-channel j is in input_buffers[j] and the data of output-channel i
+    // is in output_buffers[i]. All the buffers must contain the same number of frames.
+    pub fn mix_planar(&self, input_buffers: &[&[I]], output_buffers: &mut [&mut [O]]) -> usize {
+        self.mix_planar_with_state(None, input_buffers, output_buffers)
+    }
+
+    // Mix the interleaved input buffer to the planar output buffers and return the number of
+    // mixed frames. See mix_interleaved and mix_planar for the layouts of the buffers.
+    pub fn mix_interleaved_to_planar(
+        &self,
+        input_buffer: &[I],
+        output_buffers: &mut [&mut [O]],
+    ) -> usize {
+        self.mix_interleaved_to_planar_with_state(None, input_buffer, output_buffers)
+    }
+
+    // Mix the planar input buffers to the interleaved output buffer and return the number of
+    // mixed frames. See mix_interleaved and mix_planar for the layouts of the buffers.
+    pub fn mix_planar_to_interleaved(
+        &self,
+        input_buffers: &[&[I]],
+        output_buffer: &mut [O],
+    ) -> usize {
+        self.mix_planar_to_interleaved_with_state(None, input_buffers, output_buffer)
+    }
+
+    // The mixing methods above, which also process the data with the state of the stream if it's
+    // given. The state is owned by MixerStream, so it can be borrowed mutably while the mixer
+    // itself is shared.
+    fn mix_with_state(
+        &self,
+        state: Option<&mut State>,
+        input_buffer: &[I],
+        output_buffer: &mut [O],
+    ) {
         assert_eq!(
             input_buffer.len(),
             self.input_channels().len(),
@@ -56,19 +245,101 @@ where
             self.output_channels().len(),
             "output slice must have the same size as the output channel's one."
         );
-        for (i, output) in output_buffer.iter_mut().enumerate() {
-            // T must implement Default that returns a zero value from default().
-            let mut value = T::Coef::default(); // Create a zero value.
-            for (j, input) in input_buffer.iter().enumerate() {
-                // T::Coef needs to implement `AddAssign` and `Mul` to make `+=` and `*` work.
-                // T needs to implement `Copy` so `*input` can be copied.
-                value += self.coefficient.get(j, i) * T::to_coefficient_value(*input);
+        self.mix_frames(
+            1,
+            state,
+            |_, j| input_buffer[j],
+            |_, i, data| output_buffer[i] = data,
+        );
+    }
+
+    fn mix_interleaved_with_state(
+        &self,
+        state: Option<&mut State>,
+        input_buffer: &[I],
+        output_buffer: &mut [O],
+    ) -> usize {
+        let input_channels = self.input_channels().len();
+        let output_channels = self.output_channels().len();
+        let frames = interleaved_frames(input_buffer, input_channels, Side::Input);
+        check_frames(
+            frames,
+            interleaved_frames(output_buffer, output_channels, Side::Output),
+        );
+        if let (Some(kernel), None) = (&self.kernel, &state) {
+            if kernel.mix(input_buffer, output_buffer) {
+                return frames;
             }
-            *output = T::from_coefficient_value(
-                value,
-                self.coefficient.would_overflow_from_coefficient_value(),
-            );
         }
+        self.mix_frames(
+            frames,
+            state,
+            |f, j| input_buffer[f * input_channels + j],
+            |f, i, data| output_buffer[f * output_channels + i] = data,
+        );
+        frames
+    }
+
+    fn mix_planar_with_state(
+        &self,
+        state: Option<&mut State>,
+        input_buffers: &[&[I]],
+        output_buffers: &mut [&mut [O]],
+    ) -> usize {
+        let frames = planar_frames(input_buffers, self.input_channels().len(), Side::Input);
+        check_frames(
+            frames,
+            planar_frames(output_buffers, self.output_channels().len(), Side::Output),
+        );
+        self.mix_frames(
+            frames,
+            state,
+            |f, j| input_buffers[j][f],
+            |f, i, data| output_buffers[i][f] = data,
+        );
+        frames
+    }
+
+    fn mix_interleaved_to_planar_with_state(
+        &self,
+        state: Option<&mut State>,
+        input_buffer: &[I],
+        output_buffers: &mut [&mut [O]],
+    ) -> usize {
+        let input_channels = self.input_channels().len();
+        let frames = interleaved_frames(input_buffer, input_channels, Side::Input);
+        check_frames(
+            frames,
+            planar_frames(output_buffers, self.output_channels().len(), Side::Output),
+        );
+        self.mix_frames(
+            frames,
+            state,
+            |f, j| input_buffer[f * input_channels + j],
+            |f, i, data| output_buffers[i][f] = data,
+        );
+        frames
+    }
+
+    fn mix_planar_to_interleaved_with_state(
+        &self,
+        state: Option<&mut State>,
+        input_buffers: &[&[I]],
+        output_buffer: &mut [O],
+    ) -> usize {
+        let output_channels = self.output_channels().len();
+        let frames = planar_frames(input_buffers, self.input_channels().len(), Side::Input);
+        check_frames(
+            frames,
+            interleaved_frames(output_buffer, output_channels, Side::Output),
+        );
+        self.mix_frames(
+            frames,
+            state,
+            |f, j| input_buffers[j][f],
+            |f, i, data| output_buffer[f * output_channels + i] = data,
+        );
+        frames
     }
 
     pub fn input_channels(&self) -> &[Channel] {
@@ -78,4 +349,1186 @@ where
     pub fn output_channels(&self) -> &[Channel] {
         self.coefficient.output_channels()
     }
+
+    // Return true if the output data is the input data in another order, or in the same order,
+    // so nothing is mixed: every input channel is copied to exactly one output channel, there
+    // is no silent output channel. The samples are only converted to the output type then.
+    pub fn is_passthrough(&self) -> bool {
+        let permutation = match &self.permutation {
+            Some(permutation) => permutation,
+            None => return false,
+        };
+        let mut copied = vec![false; self.input_channels().len()];
+        permutation.len() == copied.len()
+            && permutation.iter().all(|input_channel| match input_channel {
+                Some(j) => !std::mem::replace(&mut copied[*j], true),
+                None => false,
+            })
+    }
+
+    // Return the NxM mixing matrix m used by the mixer, where m[i][j] is the coefficient for
+    // mixing input-channel j to output-channel i. The coefficients are the normalized ones in
+    // f64, before they are quantized to the coefficient type of O::Native.
+    pub fn matrix(&self) -> Vec<Vec<f64>> {
+        self.coefficient.normalized_matrix().to_vec()
+    }
+
+    // Return the coefficients for mixing all the input channels to the given output channel,
+    // in the order of the input channels, or None if the channel is not in the output channels.
+    pub fn output_coefficients(&self, output_channel: Channel) -> Option<&[f64]> {
+        let i = self
+            .output_channels()
+            .iter()
+            .position(|channel| channel == &output_channel)?;
+        Some(&self.coefficient.normalized_matrix()[i])
+    }
+
+    // Return the coefficients for mixing the given input channel to all the output channels,
+    // in the order of the output channels, or None if the channel is not in the input channels.
+    pub fn input_coefficients(&self, input_channel: Channel) -> Option<Vec<f64>> {
+        let j = self
+            .input_channels()
+            .iter()
+            .position(|channel| channel == &input_channel)?;
+        Some(
+            self.coefficient
+                .normalized_matrix()
+                .iter()
+                .map(|row| row[j])
+                .collect(),
+        )
+    }
+
+    // Return the permutation if the matrix only reorders the channels, or the SIMD kernel for
+    // the sample types otherwise.
+    fn create_fast_paths(
+        coefficient: &Coefficient<O::Native>,
+    ) -> (Option<Vec<Option<usize>>>, Option<Kernel>) {
+        match coefficient.permutation() {
+            Some(permutation) => (Some(permutation), None),
+            None => (
+                None,
+                Kernel::create::<I, O>(
+                    coefficient.normalized_matrix(),
+                    coefficient.would_overflow_from_coefficient_value(),
+                ),
+            ),
+        }
+    }
+
+    // Mix the given number of frames. The data of input-channel j in frame f is read by
+    // input(f, j) and the mixed data of output-channel i in frame f is written by
+    // output(f, i, data). The mixed data is processed with the state if it's given. The buffer
+    // sizes must be validated before calling this.
+    fn mix_frames<R, W>(&self, frames: usize, state: Option<&mut State>, input: R, mut output: W)
+    where
+        R: Fn(usize, usize) -> I,
+        W: FnMut(usize, usize, O),
+    {
+        let output_channels = self.output_channels().len();
+        let would_overflow = self.coefficient.would_overflow_from_coefficient_value();
+        if let (Some(permutation), None) = (&self.permutation, &state) {
+            let silence = O::from_native(O::Native::from_coefficient_value(
+                <O::Native as MixingCoefficient>::Coef::default(),
+                would_overflow,
+            ));
+            for f in 0..frames {
+                for (i, input_channel) in permutation.iter().enumerate() {
+                    let data = match input_channel {
+                        Some(j) => {
+                            O::from_native(O::Native::from_sample(I::to_native(input(f, *j))))
+                        }
+                        None => silence,
+                    };
+                    output(f, i, data);
+                }
+            }
+            return;
+        }
+        let mut frame = InputFrame::<I, O>::new(self.input_channels().len());
+        let would_overflow = frame.would_overflow(would_overflow);
+        let (mut ditherer, mut protector) = match state {
+            Some(State::Dither(ditherer)) => (Some(ditherer), None),
+            Some(State::Protection(protector)) => (None, Some(protector)),
+            None => (None, None),
+        };
+        // The copied data is lossless if it's not converted to another type, so it's neither
+        // mixed nor dithered. It's only dithered when it's quantized to another type.
+        let copy_directly = ditherer.is_none() || !frame.is_converted();
+        for f in 0..frames {
+            frame.read(|j| input(f, j));
+            for i in 0..output_channels {
+                let data = match (self.coefficient.row(i), &mut ditherer) {
+                    (Row::Copy(j), _) if copy_directly => {
+                        O::Native::from_sample(I::to_native(input(f, j)))
+                    }
+                    (_, Some(ditherer)) => {
+                        let value = frame.mix(&self.coefficient, i, |j| input(f, j));
+                        Self::quantize_with_dither(value, i, ditherer)
+                    }
+                    (_, None) => {
+                        let value = frame.mix(&self.coefficient, i, |j| input(f, j));
+                        O::Native::from_coefficient_value(value, would_overflow)
+                    }
+                };
+                match &mut protector {
+                    Some(protector) => protector.frame_mut()[i] = O::Native::to_f64_sample(data),
+                    None => output(f, i, O::from_native(data)),
+                }
+            }
+            // The protected data is written after the whole frame is processed.
+            if let Some(protector) = &mut protector {
+                protector.process();
+                for (i, data) in protector.frame_mut().iter().enumerate() {
+                    output(f, i, O::from_native(O::Native::from_f64_sample(*data)));
+                }
+            }
+        }
+    }
+
+    // Add the dither offset to the mixed value of the given output channel and convert it to
+    // the output sample. The offset may push the value out of range, so it's always clipped.
+    fn quantize_with_dither(
+        value: <O::Native as MixingCoefficient>::Coef,
+        channel: usize,
+        ditherer: &mut Ditherer,
+    ) -> O::Native {
+        let mut dithered = value;
+        dithered += O::Native::coefficient_from_f64(ditherer.offset(channel));
+        let data = O::Native::from_coefficient_value(dithered, Some(true));
+        let quantized =
+            O::Native::to_coefficient_value(data) * O::Native::coefficient_from_f64(1.0);
+        ditherer.feedback(
+            channel,
+            O::Native::coefficient_value_to_f64(quantized)
+                - O::Native::coefficient_value_to_f64(value),
+        );
+        data
+    }
+}
+
+// The data of an input frame to mix. When I and O have different native types, each input sample
+// is converted to f64 once per frame, without being clipped into the range of O, and the output
+// values are mixed in f64, so they are only rounded and clipped once when they are quantized to
+// O. Otherwise, the input samples are mixed in the Coef of O::Native directly, which is exact.
+struct InputFrame<I, O> {
+    converted: Option<Vec<f64>>,
+    types: PhantomData<(I, O)>,
+}
+
+impl<I, O> InputFrame<I, O>
+where
+    I: Sample,
+    O: Sample,
+{
+    fn new(input_channels: usize) -> Self {
+        let converting = TypeId::of::<I::Native>() != TypeId::of::<O::Native>();
+        Self::with_conversion(input_channels, converting)
+    }
+
+    // Create a frame whose samples are converted to f64 if converting is true, even if I and O
+    // have the same native type.
+    fn with_conversion(input_channels: usize, converting: bool) -> Self {
+        Self {
+            converted: if converting {
+                Some(vec![0.0; input_channels])
+            } else {
+                None
+            },
+            types: PhantomData,
+        }
+    }
+
+    // Read the frame, where the data of input-channel j is read by input(j). The input must be
+    // read by mix below as well, which only reads it when the samples are not converted.
+    fn read<R: Fn(usize) -> I>(&mut self, input: R) {
+        if let Some(converted) = &mut self.converted {
+            for (j, data) in converted.iter_mut().enumerate() {
+                *data = I::Native::to_f64_sample(I::to_native(input(j)));
+            }
+        }
+    }
+
+    fn is_converted(&self) -> bool {
+        self.converted.is_some()
+    }
+
+    // Return the mixed value of output-channel i in the frame.
+    fn mix<R: Fn(usize) -> I>(
+        &self,
+        coefficient: &Coefficient<O::Native>,
+        i: usize,
+        input: R,
+    ) -> <O::Native as MixingCoefficient>::Coef {
+        match self.converted {
+            Some(_) => O::Native::coefficient_value_from_f64_sample(self.mix_f64(coefficient, i)),
+            None => mix_value::<I, O, _>(coefficient, i, input),
+        }
+    }
+
+    // Return the value of output-channel i mixed in f64, in the same full scale as
+    // MixingCoefficient::to_f64_sample, which is neither rounded nor clipped. The samples must be
+    // converted.
+    fn mix_f64(&self, coefficient: &Coefficient<O::Native>, i: usize) -> f64 {
+        let converted = self
+            .converted
+            .as_ref()
+            .expect("the samples must be converted to f64.");
+        let coefficients = &coefficient.normalized_matrix()[i];
+        match coefficient.row(i) {
+            Row::Zero => 0.0,
+            Row::Copy(j) => converted[j],
+            Row::Sum(triplets) => triplets
+                .iter()
+                .map(|triplet| coefficients[triplet.input] * converted[triplet.input])
+                .sum(),
+        }
+    }
+
+    // The converted input samples are not clipped, so the mixed value may overflow even if the
+    // matrix would not make the values in range overflow.
+    fn would_overflow(&self, would_overflow: Option<bool>) -> Option<bool> {
+        match self.converted {
+            Some(_) => would_overflow.map(|_| true),
+            None => would_overflow,
+        }
+    }
+}
+
+// Return the mixed value of output-channel i, where the data of input-channel j is read by
+// input(j).
+fn mix_value<I, O, R>(
+    coefficient: &Coefficient<O::Native>,
+    i: usize,
+    input: R,
+) -> <O::Native as MixingCoefficient>::Coef
+where
+    I: Sample,
+    O: Sample,
+    R: Fn(usize) -> I,
+{
+    let read = |j| O::Native::to_coefficient_value(O::Native::from_sample(I::to_native(input(j))));
+    // Coef must implement Default that returns a zero value from default().
+    let mut value = <O::Native as MixingCoefficient>::Coef::default(); // Create a zero value.
+
+    // Only the non-zero coefficients in the execution plan are multiplied.
+    match coefficient.row(i) {
+        Row::Zero => {}
+        Row::Copy(j) => value = read(j) * O::Native::coefficient_from_f64(1.0),
+        Row::Sum(triplets) => {
+            for triplet in triplets {
+                // Coef needs to implement `AddAssign` and `Mul` to make `+=` and `*` work.
+                value += triplet.coefficient * read(triplet.input);
+            }
+        }
+    }
+    value
+}
+
+// Return the number of frames in an interleaved buffer with the given number of channels.
+fn interleaved_frames<T>(buffer: &[T], channels: usize, side: Side) -> usize {
+    assert!(channels > 0, "{} channels must not be empty.", side);
+    assert_eq!(
+        buffer.len() % channels,
+        0,
+        "{} slice must contain complete frames.",
+        side
+    );
+    buffer.len() / channels
+}
+
+// Return the number of frames in the planar buffers with the given number of channels.
+fn planar_frames<B: AsRef<[T]>, T>(buffers: &[B], channels: usize, side: Side) -> usize {
+    assert!(channels > 0, "{} channels must not be empty.", side);
+    assert_eq!(
+        buffers.len(),
+        channels,
+        "{} slices must have the same number as the {} channel's one.",
+        side,
+        side
+    );
+    let frames = buffers[0].as_ref().len();
+    assert!(
+        buffers.iter().all(|buffer| buffer.as_ref().len() == frames),
+        "{} slices must have the same number of frames.",
+        side
+    );
+    frames
+}
+
+fn check_frames(input_frames: usize, output_frames: usize) {
+    assert_eq!(
+        input_frames, output_frames,
+        "input and output slice must have the same number of frames."
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_new_with_duplicate_channels() {
+        let channels = [Channel::FrontLeft, Channel::FrontRight];
+        let duplicate = [Channel::FrontLeft, Channel::Silence, Channel::FrontLeft];
+
+        let error = Mixer::<f32>::try_new(&duplicate, &channels).unwrap_err();
+        assert_eq!(
+            error,
+            MixerError::DuplicateNonSilenceChannel {
+                side: Side::Input,
+                channel: Channel::FrontLeft,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "duplicate non-silence channel FrontLeft in input layout"
+        );
+
+        let error = Mixer::<i16>::try_new(&channels, &duplicate).unwrap_err();
+        assert_eq!(
+            error,
+            MixerError::DuplicateNonSilenceChannel {
+                side: Side::Output,
+                channel: Channel::FrontLeft,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_with_duplicate_channels() {
+        let channels = [Channel::FrontLeft, Channel::FrontRight];
+        let duplicate = [Channel::FrontRight, Channel::FrontRight];
+        let _ = Mixer::<f32>::new(&duplicate, &channels);
+    }
+
+    #[test]
+    fn test_new_with_layout_presets() {
+        let presets = [
+            (ChannelLayout::mono(), 1),
+            (ChannelLayout::stereo(), 2),
+            (ChannelLayout::surround_2_1(), 3),
+            (ChannelLayout::surround_3_0(), 3),
+            (ChannelLayout::quad(), 4),
+            (ChannelLayout::quad_side(), 4),
+            (ChannelLayout::surround_5_0(), 5),
+            (ChannelLayout::surround_5_0_back(), 5),
+            (ChannelLayout::surround_5_1(), 6),
+            (ChannelLayout::surround_5_1_back(), 6),
+            (ChannelLayout::surround_6_1(), 7),
+            (ChannelLayout::surround_7_1(), 8),
+            (ChannelLayout::surround_5_1_2_back(), 8),
+            (ChannelLayout::surround_7_1_4(), 12),
+        ];
+        for (input_layout, input_channels) in presets.iter() {
+            assert_eq!(input_layout.len(), *input_channels);
+            // Channels in the presets are ordered by the values defined in enum Channel.
+            assert!(input_layout
+                .windows(2)
+                .all(|pair| pair[0].number() < pair[1].number()));
+            for (output_layout, _) in presets.iter() {
+                let mixer = Mixer::<f32>::try_new(input_layout, output_layout).unwrap();
+                assert_eq!(mixer.input_channels(), input_layout.channels());
+                assert_eq!(mixer.output_channels(), output_layout.channels());
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_matrix_i16() {
+        // Mix the back-center channel into the front-stereo channels with inverted phase, so the
+        // row sums don't exceed 1 but the absolute row sums do and the mixed data is clipped.
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight, Channel::BackCenter];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let matrix = vec![vec![1.0, 0.0, -0.5], vec![0.0, 1.0, -0.5]];
+        let mixer = Mixer::<i16>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+
+        let mut output_buffer = [0; 2];
+        mixer.mix(&[0x7FFF, 0x4000, -0x4000], &mut output_buffer);
+        assert_eq!(output_buffer, [0x7FFF, 0x6000]);
+        mixer.mix(&[-0x8000, -0x4000, 0x4000], &mut output_buffer);
+        assert_eq!(output_buffer, [-0x8000, -0x6000]);
+    }
+
+    #[test]
+    fn test_from_matrix_i32() {
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight, Channel::BackCenter];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let matrix = vec![vec![1.0, 0.0, -0.5], vec![0.0, 1.0, -0.5]];
+        let mixer = Mixer::<i32>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+
+        let mut output_buffer = [0; 2];
+        mixer.mix(&[i32::MAX, 0x4000_0000, -0x4000_0000], &mut output_buffer);
+        assert_eq!(output_buffer, [i32::MAX, 0x6000_0000]);
+        mixer.mix(&[i32::MIN, -0x4000_0000, 0x4000_0000], &mut output_buffer);
+        assert_eq!(output_buffer, [i32::MIN, -0x6000_0000]);
+    }
+
+    #[test]
+    fn test_from_matrix_i24() {
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight, Channel::BackCenter];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let matrix = vec![vec![1.0, 0.0, -0.5], vec![0.0, 1.0, -0.5]];
+
+        let mixer = Mixer::<I24>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+        let mut output_buffer = [I24::default(); 2];
+        mixer.mix(
+            &[
+                I24::new(I24::MAX),
+                I24::new(0x40_0000),
+                I24::new(-0x40_0000),
+            ],
+            &mut output_buffer,
+        );
+        assert_eq!(output_buffer, [I24::new(I24::MAX), I24::new(0x60_0000)]);
+        mixer.mix(
+            &[
+                I24::new(I24::MIN),
+                I24::new(-0x40_0000),
+                I24::new(0x40_0000),
+            ],
+            &mut output_buffer,
+        );
+        assert_eq!(output_buffer, [I24::new(I24::MIN), I24::new(-0x60_0000)]);
+
+        // The high bytes of the input containers are ignored and the output samples are
+        // sign-extended.
+        let mixer =
+            Mixer::<I24In32>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+        let mut output_buffer = [I24In32::default(); 2];
+        mixer.mix(
+            &[
+                I24In32::from_container(0x00FF_FFFF),
+                I24In32::from_container(0x7F40_0000),
+                I24In32::from_container(0x00C0_0000),
+            ],
+            &mut output_buffer,
+        );
+        assert_eq!(
+            output_buffer
+                .iter()
+                .map(|s| s.to_container())
+                .collect::<Vec<_>>(),
+            vec![0x20_0000 - 1, 0x60_0000]
+        );
+    }
+
+    #[test]
+    fn test_from_matrix_u8() {
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight, Channel::BackCenter];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let matrix = vec![vec![1.0, 0.0, -0.5], vec![0.0, 1.0, -0.5]];
+        let mixer = Mixer::<u8>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+
+        // 128 is the zero level.
+        let mut output_buffer = [0; 2];
+        mixer.mix(&[128, 128, 128], &mut output_buffer);
+        assert_eq!(output_buffer, [128, 128]);
+        mixer.mix(&[255, 128 + 0x40, 128 - 0x40], &mut output_buffer);
+        assert_eq!(output_buffer, [255, 128 + 0x60]);
+        mixer.mix(&[0, 128 - 0x40, 128 + 0x40], &mut output_buffer);
+        assert_eq!(output_buffer, [0, 128 - 0x60]);
+    }
+
+    #[test]
+    fn test_matrix() {
+        use std::f64::consts::FRAC_1_SQRT_2;
+
+        let input_layout = ChannelLayout::surround_5_1();
+        let output_layout = ChannelLayout::stereo();
+
+        let mixer = Mixer::<f32>::new(&input_layout, &output_layout);
+        let matrix = mixer.matrix();
+        assert_eq!(
+            matrix,
+            vec![
+                vec![1.0, 0.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0],
+                vec![0.0, 1.0, FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2],
+            ]
+        );
+        assert_eq!(
+            mixer.output_coefficients(Channel::FrontRight),
+            Some(matrix[1].as_slice())
+        );
+        assert_eq!(
+            mixer.input_coefficients(Channel::SideLeft),
+            Some(vec![FRAC_1_SQRT_2, 0.0])
+        );
+        assert_eq!(mixer.output_coefficients(Channel::FrontCenter), None);
+        assert_eq!(mixer.input_coefficients(Channel::BackLeft), None);
+
+        // The matrix for i16 is normalized so every row sum is not greater than 1.
+        let mixer = Mixer::<i16>::new(&input_layout, &output_layout);
+        for row in mixer.matrix() {
+            assert!(row.iter().sum::<f64>() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_mix_interleaved_f32() {
+        test_mix_interleaved::<f32>(|i| i as f32 * 0.01);
+    }
+
+    #[test]
+    fn test_mix_interleaved_i16() {
+        test_mix_interleaved::<i16>(|i| (i * 997) as i16);
+    }
+
+    #[test]
+    fn test_mix_interleaved_i32() {
+        test_mix_interleaved::<i32>(|i| (i as i32).wrapping_mul(0x0765_4321));
+    }
+
+    #[test]
+    fn test_mix_interleaved_u8() {
+        test_mix_interleaved::<u8>(|i| (i * 37) as u8);
+    }
+
+    #[test]
+    fn test_mix_interleaved_f64() {
+        test_mix_interleaved::<f64>(|i| i as f64 * 0.01);
+    }
+
+    #[test]
+    fn test_mix_interleaved_i24() {
+        test_mix_interleaved::<I24>(|i| I24::new(((i * 0x07_6543) & 0xFF_FFFF) as i32 - 0x80_0000));
+        test_mix_interleaved::<I24In32>(|i| {
+            I24In32::new(((i * 0x07_6543) & 0xFF_FFFF) as i32 - 0x80_0000)
+        });
+    }
+
+    fn test_mix_interleaved<T>(sample: fn(usize) -> T)
+    where
+        T: Default + PartialEq + Sample,
+    {
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::BackLeft,
+            Channel::BackRight,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::Silence, Channel::FrontRight];
+        let frames = 16;
+        let mixer = Mixer::<T>::new(&input_channels, &output_channels);
+
+        let input_buffer: Vec<T> = (0..frames * input_channels.len()).map(sample).collect();
+        let mut output_buffer = vec![T::default(); frames * output_channels.len()];
+        assert_eq!(
+            mixer.mix_interleaved(&input_buffer, &mut output_buffer),
+            frames
+        );
+
+        let mut expected = vec![T::default(); output_channels.len()];
+        for (input, output) in input_buffer
+            .chunks(input_channels.len())
+            .zip(output_buffer.chunks(output_channels.len()))
+        {
+            mixer.mix(input, &mut expected);
+            assert_eq!(output, expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_passthrough() {
+        // Reorder 5.1 in the WAVE order to the order with the surround channels first.
+        let input_channels = ChannelLayout::surround_5_1();
+        let input_channels = input_channels.channels();
+        let output_channels: Vec<Channel> = [4, 5, 0, 1, 2, 3]
+            .iter()
+            .map(|j| input_channels[*j])
+            .collect();
+        let frames = 64;
+
+        let mixer = Mixer::<i16>::new(input_channels, &output_channels);
+        assert!(mixer.is_passthrough());
+        let input: Vec<i16> = (0..frames * 6).map(|i| (i * 1021) as i16).collect();
+        let mut output = vec![0; frames * 6];
+        mixer.mix_interleaved(&input, &mut output);
+        for (input, output) in input.chunks(6).zip(output.chunks(6)) {
+            assert_eq!(
+                output,
+                [input[4], input[5], input[0], input[1], input[2], input[3]]
+            );
+        }
+
+        // The samples are converted losslessly, and the silent output channel is written.
+        let mixer = Mixer::<u8>::new(
+            &[Channel::FrontLeft, Channel::FrontRight],
+            &[Channel::FrontRight, Channel::Silence, Channel::FrontLeft],
+        );
+        assert!(!mixer.is_passthrough());
+        let mut output = [0; 3];
+        mixer.mix(&[0, 255], &mut output);
+        assert_eq!(output, [255, 128, 0]);
+        let mixer = Mixer::<i16, f32>::new(input_channels, input_channels);
+        assert!(mixer.is_passthrough());
+        let mut output = vec![0.0; frames * 6];
+        mixer.mix_interleaved(&input, &mut output);
+        for (input, output) in input.iter().zip(&output) {
+            assert_eq!(*output, f32::from(*input) / 32768.0);
+        }
+
+        // The data is mixed if it's not only reordered.
+        let mixer = Mixer::<f32>::new(&ChannelLayout::mono(), &ChannelLayout::stereo());
+        assert!(!mixer.is_passthrough());
+        let mixer = Mixer::<f32>::from_matrix(
+            &[Channel::FrontLeft, Channel::FrontRight],
+            &[Channel::FrontLeft, Channel::FrontRight],
+            &[vec![1.0, 0.0], vec![1.0, 0.0]],
+        )
+        .unwrap();
+        assert!(!mixer.is_passthrough());
+        let mut output = [0.0; 2];
+        mixer.mix(&[0.25, 0.5], &mut output);
+        assert_eq!(output, [0.25, 0.25]);
+    }
+
+    #[test]
+    fn test_mix_interleaved_f32_kernel() {
+        // The f32 frames are mixed by the SIMD kernel, whose chunks don't align with the frames
+        // here, and the result is the same as the one mixed frame by frame.
+        let input_channels = ChannelLayout::surround_7_1_4();
+        let output_channels = ChannelLayout::surround_5_1();
+        let mixer = Mixer::<f32>::new(input_channels.channels(), output_channels.channels());
+        let (m, n) = (
+            input_channels.channels().len(),
+            output_channels.channels().len(),
+        );
+        let frames = 13;
+        let input_buffer: Vec<f32> = (0..frames * m)
+            .map(|i| ((i * 53) % 97) as f32 / 48.0 - 1.0)
+            .collect();
+        let mut output_buffer = vec![0.0; frames * n];
+        mixer.mix_interleaved(&input_buffer, &mut output_buffer);
+
+        let mut expected = vec![0.0; n];
+        for (input, output) in input_buffer.chunks(m).zip(output_buffer.chunks(n)) {
+            mixer.mix(input, &mut expected);
+            assert_eq!(output, expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_mix_add() {
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let stereo_channels = [Channel::FrontLeft, Channel::FrontRight];
+
+        // Adding to the silent output is the same as mixing without gain.
+        let mixer = Mixer::<i16>::new(&input_channels, &output_channels);
+        let input: Vec<i16> = (0..16 * 3).map(|i| (i * 997) as i16).collect();
+        let mut expected = vec![0; 16 * 2];
+        mixer.mix_interleaved(&input, &mut expected);
+        let mut output = vec![0; 16 * 2];
+        assert_eq!(mixer.mix_add(&input, &mut output, 1.0), 16);
+        assert_eq!(output, expected);
+
+        let mixer = Mixer::<u8>::new(&input_channels, &output_channels);
+        let input: Vec<u8> = (0..16 * 3).map(|i| (i * 37) as u8).collect();
+        let mut expected = vec![0; 16 * 2];
+        mixer.mix_interleaved(&input, &mut expected);
+        let mut output = vec![128; 16 * 2];
+        mixer.mix_add(&input, &mut output, 1.0);
+        assert_eq!(output, expected);
+
+        // Two streams are summed into the same output buffer with their gains.
+        let surround = Mixer::<f32>::new(&input_channels, &output_channels);
+        let stereo = Mixer::<f32>::new(&stereo_channels, &output_channels);
+        let surround_input = [0.5, 0.25, 0.125];
+        let stereo_input = [0.25, -0.5];
+        let mut output = [0.0; 2];
+        surround.mix_add(&surround_input, &mut output, 1.0);
+        stereo.mix_add(&stereo_input, &mut output, 0.5);
+        let mut surround_output = [0.0; 2];
+        surround.mix(&surround_input, &mut surround_output);
+        assert_eq!(
+            output,
+            [surround_output[0] + 0.125, surround_output[1] - 0.25]
+        );
+
+        // The integer sums are clipped.
+        let stereo = Mixer::<i16>::new(&stereo_channels, &output_channels);
+        let mut output = [0x6000, -0x6000];
+        stereo.mix_add(&[0x4000, -0x4000], &mut output, 1.0);
+        assert_eq!(output, [i16::MAX, i16::MIN]);
+        let mut output = [0x6000, -0x6000];
+        stereo.mix_add(&[0x4000, -0x4000], &mut output, -0.5);
+        assert_eq!(output, [0x4000, -0x4000]);
+
+        let stereo = Mixer::<i32>::new(&stereo_channels, &output_channels);
+        let mut output = [0x6000_0000, -0x6000_0000];
+        stereo.mix_add(&[0x4000_0000, -0x4000_0000], &mut output, 1.0);
+        assert_eq!(output, [i32::MAX, i32::MIN]);
+
+        let stereo = Mixer::<I24>::new(&stereo_channels, &output_channels);
+        let mut output = [I24::new(0x60_0000), I24::new(-0x60_0000)];
+        stereo.mix_add(
+            &[I24::new(0x40_0000), I24::new(-0x40_0000)],
+            &mut output,
+            1.0,
+        );
+        assert_eq!(output, [I24::new(I24::MAX), I24::new(I24::MIN)]);
+    }
+
+    #[test]
+    fn test_mix_planar_f32() {
+        test_mix_planar::<f32>(|i| i as f32 * 0.01);
+    }
+
+    #[test]
+    fn test_mix_planar_i16() {
+        test_mix_planar::<i16>(|i| (i * 997) as i16);
+    }
+
+    fn test_mix_planar<T>(sample: fn(usize) -> T)
+    where
+        T: Default + PartialEq + Sample,
+    {
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ];
+        let output_channels = [Channel::FrontRight, Channel::FrontLeft];
+        let frames = 16;
+        let mixer = Mixer::<T>::new(&input_channels, &output_channels);
+
+        let interleaved_input: Vec<T> = (0..frames * input_channels.len()).map(sample).collect();
+        let mut expected = vec![T::default(); frames * output_channels.len()];
+        mixer.mix_interleaved(&interleaved_input, &mut expected);
+
+        let planar_input: Vec<Vec<T>> = (0..input_channels.len())
+            .map(|j| {
+                interleaved_input
+                    .iter()
+                    .skip(j)
+                    .step_by(input_channels.len())
+                    .copied()
+                    .collect()
+            })
+            .collect();
+        let planar_input: Vec<&[T]> = planar_input.iter().map(|plane| plane.as_slice()).collect();
+
+        let check_planar = |output: &[Vec<T>]| {
+            for (i, plane) in output.iter().enumerate() {
+                for (f, data) in plane.iter().enumerate() {
+                    assert_eq!(*data, expected[f * output_channels.len() + i]);
+                }
+            }
+        };
+
+        let mut output = vec![vec![T::default(); frames]; output_channels.len()];
+        {
+            let mut planes: Vec<&mut [T]> = output
+                .iter_mut()
+                .map(|plane| plane.as_mut_slice())
+                .collect();
+            assert_eq!(mixer.mix_planar(&planar_input, &mut planes), frames);
+        }
+        check_planar(&output);
+
+        let mut output = vec![vec![T::default(); frames]; output_channels.len()];
+        {
+            let mut planes: Vec<&mut [T]> = output
+                .iter_mut()
+                .map(|plane| plane.as_mut_slice())
+                .collect();
+            assert_eq!(
+                mixer.mix_interleaved_to_planar(&interleaved_input, &mut planes),
+                frames
+            );
+        }
+        check_planar(&output);
+
+        let mut output = vec![T::default(); frames * output_channels.len()];
+        assert_eq!(
+            mixer.mix_planar_to_interleaved(&planar_input, &mut output),
+            frames
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_mix_i16_to_f32() {
+        // The f32 output is the same as the one mixed from the f32 input converted from i16,
+        // except the rounding of the f32 mixing.
+        let input: Vec<i16> = (0..16 * 6).map(|i| (i * 997) as i16).collect();
+        let converted: Vec<f32> = input.iter().map(|s| f32::from(*s) / 32768.0).collect();
+        test_mix_converted::<i16, f32>(&input, &converted, 1e-6);
+    }
+
+    #[test]
+    fn test_mix_f32_to_i16() {
+        // The output is within 1 LSB of the one mixed from the i16 input converted from f32,
+        // which is rounded before it's mixed.
+        let input: Vec<f32> = (0..16 * 6).map(|i| i as f32 * 0.02 - 0.95).collect();
+        let converted: Vec<i16> = input.iter().map(|s| (s * 32768.0).round() as i16).collect();
+        test_mix_converted::<f32, i16>(&input, &converted, 1.0 / 32768.0);
+    }
+
+    #[test]
+    fn test_mix_loud_f32_to_i16() {
+        // The out-of-range input is not clipped before it's mixed, so only the mixed value is
+        // clipped into the range of i16.
+        let mixer = Mixer::<f32, i16>::from_matrix(
+            &[Channel::FrontLeft, Channel::FrontRight],
+            &[Channel::FrontCenter],
+            &[vec![0.5, 0.5]],
+        )
+        .unwrap();
+        let mut output = [0; 1];
+        mixer.mix(&[1.5, 0.25], &mut output);
+        assert_eq!(output, [28672]);
+        mixer.mix(&[3.0, 1.5], &mut output);
+        assert_eq!(output, [i16::MAX]);
+        mixer.mix(&[-3.0, 1.5], &mut output);
+        assert_eq!(output, [-24576]);
+    }
+
+    #[test]
+    fn test_mix_i24_to_i32() {
+        let input: Vec<I24> = (0..16 * 6)
+            .map(|i| I24::new(((i * 0x07_6543) & 0xFF_FFFF) - 0x80_0000))
+            .collect();
+        let converted: Vec<i32> = input.iter().map(|s| s.to_i32() << 8).collect();
+        test_mix_converted::<I24, i32>(&input, &converted, 1.0 / 2_147_483_648.0);
+    }
+
+    // The output of the mixer converting I to O must be within the given tolerance, in the full
+    // scale of O, from the output of the O mixer mixing the converted input.
+    fn test_mix_converted<I, O>(input_buffer: &[I], converted_buffer: &[O], tolerance: f64)
+    where
+        I: Sample,
+        O: Default + PartialEq + Sample,
+    {
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+            Channel::LowFrequency,
+            Channel::SideLeft,
+            Channel::SideRight,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let mixer = Mixer::<I, O>::new(&input_channels, &output_channels);
+        let same_type_mixer = Mixer::<O>::new(&input_channels, &output_channels);
+        assert_eq!(mixer.matrix(), same_type_mixer.matrix());
+
+        let frames = input_buffer.len() / input_channels.len();
+        let mut output_buffer = vec![O::default(); frames * output_channels.len()];
+        assert_eq!(
+            mixer.mix_interleaved(input_buffer, &mut output_buffer),
+            frames
+        );
+        let mut expected = vec![O::default(); frames * output_channels.len()];
+        same_type_mixer.mix_interleaved(converted_buffer, &mut expected);
+        let to_f64 = |data: &O| O::Native::to_f64_sample(O::to_native(*data));
+        for (data, expected) in output_buffer.iter().zip(expected.iter()) {
+            assert!(
+                (to_f64(data) - to_f64(expected)).abs() <= tolerance,
+                "{:?} is not close to {:?}",
+                data,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_mix_with_dither() {
+        let frames = 4096;
+        // Mix the 1-LSB center channel into the silent stereo channels at 0.3, which is
+        // normalized to 0.3 / 1.3 since the row sums are not greater than 1 for i16. The mixed
+        // level is about 0.23 LSB, which is always rounded to 0 without dither.
+        let level = 0.3 / 1.3;
+        let input: Vec<i16> = (0..frames * 3).map(|i| (i % 3 / 2) as i16).collect();
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let mix = |config: &MixerConfig| {
+            let mut stream = MixerStream::<i16>::with_config(
+                &input_channels,
+                &output_channels,
+                &config.clone().with_center_mix_level(0.3),
+            )
+            .unwrap();
+            let mut output = vec![0; frames * 2];
+            stream.mix_interleaved(&input, &mut output);
+            output
+        };
+        let mean = |output: &[i16]| {
+            output.iter().map(|data| f64::from(*data)).sum::<f64>() / output.len() as f64
+        };
+
+        assert!(mix(&MixerConfig::default()).iter().all(|data| *data == 0));
+
+        // The dither makes the average output level match the mixed level.
+        for dither in [Dither::Rectangular, Dither::Triangular].iter() {
+            let config = MixerConfig::default().with_dither(*dither);
+            let output = mix(&config);
+            assert!(output.iter().all(|data| (-1..=1).contains(data)));
+            assert!((mean(&output) - level).abs() < 0.05);
+            // The output is reproducible with the same seed.
+            assert_eq!(output, mix(&config));
+            assert_ne!(output, mix(&config.clone().with_dither_seed(1)));
+
+            // The state is kept between the mixing calls of the stream.
+            let mut stream =
+                MixerStream::<i16>::with_config(&input_channels, &output_channels, &config)
+                    .unwrap();
+            let mut halves = vec![0; frames * 2];
+            let (first, second) = halves.split_at_mut(frames);
+            stream.mix_interleaved(&input[..frames * 3 / 2], first);
+            stream.mix_interleaved(&input[frames * 3 / 2..], second);
+            let mut stream =
+                MixerStream::<i16>::with_config(&input_channels, &output_channels, &config)
+                    .unwrap();
+            let mut whole = vec![0; frames * 2];
+            stream.mix_interleaved(&input, &mut whole);
+            assert_eq!(halves, whole);
+
+            // The mixer without a stream can't keep the state.
+            assert_eq!(
+                Mixer::<i16>::with_config(&input_channels, &output_channels, &config).unwrap_err(),
+                MixerError::StatefulConfig
+            );
+        }
+
+        // With the noise shaping, the accumulated quantization error is bounded, so the
+        // quantization noise has no DC component.
+        let output = mix(&MixerConfig::default().with_noise_shaping(true));
+        let mut error = 0.0;
+        for data in output.iter().step_by(2) {
+            error += f64::from(*data) - level;
+            assert!(error.abs() <= 1.0);
+        }
+        let output = mix(&MixerConfig::default()
+            .with_dither(Dither::Triangular)
+            .with_noise_shaping(true));
+        assert!((mean(&output) - level).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_mix_passthrough_with_dither() {
+        // The copied data is lossless, so it's not dithered.
+        let channels = [Channel::FrontLeft, Channel::FrontRight];
+        let config = MixerConfig::default().with_dither(Dither::Triangular);
+        let input: Vec<i16> = (0..1024).map(|i| (i * 37 % 201 - 100) as i16).collect();
+        let mut output = vec![0; 1024];
+        let mut stream = MixerStream::<i16>::with_config(&channels, &channels, &config).unwrap();
+        stream.mix_interleaved(&input, &mut output);
+        assert_eq!(output, input);
+
+        // So is the reordered data.
+        let reversed = [Channel::FrontRight, Channel::FrontLeft];
+        let mut stream = MixerStream::<i16>::with_config(&channels, &reversed, &config).unwrap();
+        stream.mix_interleaved(&input, &mut output);
+        for (input, output) in input.chunks(2).zip(output.chunks(2)) {
+            assert_eq!(output, [input[1], input[0]]);
+        }
+    }
+
+    #[test]
+    fn test_mix_with_dither_clipped() {
+        // The dither never makes the nearly full-scale samples wrap around.
+        let channels = [Channel::FrontLeft, Channel::FrontRight];
+        let config = MixerConfig::default()
+            .with_dither(Dither::Triangular)
+            .with_noise_shaping(true);
+        let matrix = [vec![0.9999, 0.0], vec![0.0, 0.9999]];
+        let mut stream =
+            MixerStream::<i16>::from_matrix(&channels, &channels, &matrix, &config).unwrap();
+        let input: Vec<i16> = (0..1024)
+            .map(|i| if i % 2 == 0 { i16::MAX } else { i16::MIN })
+            .collect();
+        let mut output = vec![0; 1024];
+        stream.mix_interleaved(&input, &mut output);
+        for (input, output) in input.iter().zip(output.iter()) {
+            assert!((i32::from(*input) - i32::from(*output)).abs() <= 8);
+        }
+
+        // The float output samples are not dithered.
+        let mut stream = MixerStream::<f32>::with_config(&channels, &channels, &config).unwrap();
+        let input = [0.1, -0.2, 0.3, -0.4];
+        let mut output = [0.0; 4];
+        stream.mix_interleaved(&input, &mut output);
+        assert_eq!(output, input);
+        let mixer = Mixer::<f32>::with_config(&channels, &channels, &config).unwrap();
+        mixer.mix_interleaved(&input, &mut output);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_mix_with_output_protection() {
+        // The stereo channels are mixed to mono at -3 dB each, so the full-scale input in both
+        // channels exceeds the range of the output.
+        let input_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let output_channels = [Channel::FrontCenter];
+        let input = vec![1.0_f32; 256];
+        let mix = |protection: OutputProtection| {
+            let config = MixerConfig::default().with_output_protection(protection);
+            let mut stream =
+                MixerStream::<f32>::with_config(&input_channels, &output_channels, &config)
+                    .unwrap();
+            let mut output = vec![0.0; 128];
+            stream.mix_interleaved(&input, &mut output);
+            (output, stream.latency())
+        };
+
+        let (output, latency) = mix(OutputProtection::None);
+        assert!(output.iter().all(|data| *data > 1.4));
+        assert_eq!(latency, 0);
+
+        let (output, _) = mix(OutputProtection::HardClip);
+        assert!(output.iter().all(|data| *data == 1.0));
+
+        let (output, _) = mix(OutputProtection::SoftClip);
+        assert!(output.iter().all(|data| *data > 0.9 && *data < 1.0));
+
+        let (output, latency) = mix(OutputProtection::Limiter {
+            threshold: 0.5,
+            lookahead: 32,
+            release: 100,
+        });
+        assert_eq!(latency, 32);
+        assert!(output[..32].iter().all(|data| *data == 0.0));
+        assert!(output[32..].iter().all(|data| *data > 0.49 && *data <= 0.5));
+
+        // The protection in the config is applied to the given matrix as well.
+        let config = MixerConfig::default().with_output_protection(OutputProtection::HardClip);
+        let mut stream = MixerStream::<f32>::from_matrix(
+            &input_channels,
+            &output_channels,
+            &[vec![1.0, 1.0]],
+            &config,
+        )
+        .unwrap();
+        let mut output = [0.0; 2];
+        stream.mix_interleaved(&[1.0, 0.5, -0.25, 0.5], &mut output);
+        assert_eq!(output, [1.0, 0.25]);
+
+        // The mixer without a stream can't keep the state.
+        let config = MixerConfig::default().with_output_protection(OutputProtection::SoftClip);
+        assert_eq!(
+            Mixer::<f32>::with_config(&input_channels, &output_channels, &config).unwrap_err(),
+            MixerError::StatefulConfig
+        );
+        let config = MixerConfig::default().with_output_protection(OutputProtection::Limiter {
+            threshold: 1.5,
+            lookahead: 32,
+            release: 100,
+        });
+        assert_eq!(
+            Mixer::<f32>::with_config(&input_channels, &output_channels, &config).unwrap_err(),
+            MixerError::InvalidLimiterThreshold { threshold: 1.5 }
+        );
+
+        // The integer output samples are always clipped without the protection.
+        let config = MixerConfig::default().with_output_protection(OutputProtection::SoftClip);
+        let stream =
+            MixerStream::<f32, i16>::with_config(&input_channels, &output_channels, &config)
+                .unwrap();
+        assert_eq!(stream.latency(), 0);
+        assert!(Mixer::<f32, i16>::with_config(&input_channels, &output_channels, &config).is_ok());
+    }
+
+    // A custom sample type for the big-endian 16-bit integer, i.e., S16BE.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    struct I16Be([u8; 2]);
+
+    impl Sample for I16Be {
+        type Native = i16;
+
+        fn to_native(value: Self) -> Self::Native {
+            i16::from_be_bytes(value.0)
+        }
+
+        fn from_native(value: Self::Native) -> Self {
+            Self(value.to_be_bytes())
+        }
+    }
+
+    fn output_channel_count<T: Sample>(mixer: &Mixer<T>) -> usize {
+        mixer.output_channels().len()
+    }
+
+    #[test]
+    fn test_mix_custom_sample() {
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+        ];
+        let output_channels = [Channel::FrontLeft, Channel::FrontRight];
+        let mixer = Mixer::<I16Be>::new(&input_channels, &output_channels);
+        assert_eq!(output_channel_count(&mixer), 2);
+
+        let input: Vec<i16> = (0..16 * 3).map(|i| (i * 1361) as i16).collect();
+        let input_be: Vec<I16Be> = input.iter().map(|s| I16Be::from_native(*s)).collect();
+        let mut output_be = vec![I16Be::default(); 16 * 2];
+        mixer.mix_interleaved(&input_be, &mut output_be);
+
+        let mut expected = vec![0; 16 * 2];
+        Mixer::<i16>::new(&input_channels, &output_channels).mix_interleaved(&input, &mut expected);
+        let expected_be: Vec<I16Be> = expected.iter().map(|s| I16Be::from_native(*s)).collect();
+        assert_eq!(output_be, expected_be);
+
+        // The custom samples can be converted from or to other sample types.
+        let mut output = vec![0.0; 16 * 2];
+        Mixer::<I16Be, f32>::new(&input_channels, &output_channels)
+            .mix_interleaved(&input_be, &mut output);
+        let mut expected = vec![0.0; 16 * 2];
+        Mixer::<i16, f32>::new(&input_channels, &output_channels)
+            .mix_interleaved(&input, &mut expected);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mix_planar_with_different_frames() {
+        let mixer = Mixer::<f32>::new(
+            &[Channel::FrontLeft, Channel::FrontRight],
+            &[Channel::FrontCenter],
+        );
+        let left = [0.0; 4];
+        let right = [0.0; 3];
+        let mut center = [0.0; 4];
+        let _ = mixer.mix_planar(&[&left, &right], &mut [&mut center]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mix_interleaved_with_different_frames() {
+        let mixer = Mixer::<f32>::new(
+            &[Channel::FrontCenter],
+            &[Channel::FrontLeft, Channel::FrontRight],
+        );
+        let input_buffer = [0.0; 4];
+        let mut output_buffer = [0.0; 6];
+        let _ = mixer.mix_interleaved(&input_buffer, &mut output_buffer);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mix_interleaved_with_incomplete_frame() {
+        let mixer = Mixer::<f32>::new(
+            &[Channel::FrontCenter],
+            &[Channel::FrontLeft, Channel::FrontRight],
+        );
+        let input_buffer = [0.0; 3];
+        let mut output_buffer = [0.0; 7];
+        let _ = mixer.mix_interleaved(&input_buffer, &mut output_buffer);
+    }
 }
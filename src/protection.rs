@@ -0,0 +1,297 @@
+use crate::coefficient::MixerError;
+use std::collections::VecDeque;
+
+// The processing applied to the float output samples to keep them in the [-1.0, 1.0] range.
+// The mixing matrix for the float samples is not normalized by default, so the mixed data can
+// exceed the range when upmixing or downmixing with custom levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputProtection {
+    // The mixed data is written as it is.
+    None,
+    // The mixed data out of range is clamped to -1.0 or 1.0.
+    HardClip,
+    // The mixed data above the knee, -6 dB, is compressed by tanh smoothly, so it never
+    // exceeds 1.0. The data below the knee is unchanged.
+    SoftClip,
+    // A lookahead peak limiter. The output is delayed by the lookahead frames, so the gain can
+    // be reduced smoothly before a peak above the threshold arrives, and then it's restored to
+    // 1.0 over about the release frames after the peak. The gain is shared by all the channels
+    // so the stereo image is kept.
+    Limiter {
+        threshold: f64,
+        lookahead: usize,
+        release: usize,
+    },
+}
+
+// The knee of the soft clip, which is -6 dB.
+const SOFT_CLIP_KNEE: f64 = 0.5;
+
+// The state of the output protection for a mixer. The data of a frame is processed in place.
+#[derive(Debug)]
+pub struct Protector {
+    protection: OutputProtection,
+    frame: Vec<f64>,
+    limiter: Option<PeakLimiter>,
+}
+
+impl Protector {
+    // Return None if there is no output protection, or an error if the limiter threshold is
+    // invalid.
+    pub fn new(protection: OutputProtection, channels: usize) -> Result<Option<Self>, MixerError> {
+        let limiter = match protection {
+            OutputProtection::None => return Ok(None),
+            OutputProtection::HardClip | OutputProtection::SoftClip => None,
+            OutputProtection::Limiter {
+                threshold,
+                lookahead,
+                release,
+            } => Some(PeakLimiter::new(threshold, lookahead, release, channels)?),
+        };
+        Ok(Some(Self {
+            protection,
+            frame: vec![0.0; channels],
+            limiter,
+        }))
+    }
+
+    // The number of frames the output is delayed by.
+    pub fn latency(&self) -> usize {
+        self.limiter
+            .as_ref()
+            .map_or(0, |limiter| limiter.delay.len())
+    }
+
+    // The data of the frame to be processed, in the order of the output channels.
+    pub fn frame_mut(&mut self) -> &mut [f64] {
+        &mut self.frame
+    }
+
+    // Process the data in the frame, which becomes the output data after return.
+    pub fn process(&mut self) {
+        match self.protection {
+            OutputProtection::None => {}
+            OutputProtection::HardClip => {
+                for data in &mut self.frame {
+                    *data = data.clamp(-1.0, 1.0);
+                }
+            }
+            OutputProtection::SoftClip => {
+                for data in &mut self.frame {
+                    *data = soft_clip(*data);
+                }
+            }
+            OutputProtection::Limiter { .. } => {
+                let limiter = self.limiter.as_mut().expect("limiter must be created");
+                limiter.process(&mut self.frame);
+            }
+        }
+    }
+}
+
+fn soft_clip(data: f64) -> f64 {
+    let magnitude = data.abs();
+    if magnitude <= SOFT_CLIP_KNEE {
+        return data;
+    }
+    // The curve has the same slope as the linear part at the knee and approaches 1.0.
+    let range = 1.0 - SOFT_CLIP_KNEE;
+    let clipped = SOFT_CLIP_KNEE + range * ((magnitude - SOFT_CLIP_KNEE) / range).tanh();
+    clipped.copysign(data)
+}
+
+// The gain needed by the frame at time t is g(t) = min(1, threshold / peak(t)). The output
+// frame at time t is the input frame at time t - L delayed by the L lookahead frames, with the
+// gain that is the average of m(k) for k in [t - L, t], where m(k) is the minimum of g in
+// [k - L, k]. All the windows of m(k) contain t - L, so the gain is never greater than
+// g(t - L) and the peak is limited, while the gain changes linearly over L frames at most.
+#[derive(Debug)]
+struct PeakLimiter {
+    threshold: f64,
+    // The delayed frames in a ring buffer with L frames.
+    delay: Vec<Vec<f64>>,
+    // The candidates of the minimum gains in the window, as (time, gain), in increasing order.
+    minimums: VecDeque<(usize, f64)>,
+    // The m(k) in the window of the average, in a ring buffer with L + 1 values, and their sum.
+    gains: Vec<f64>,
+    gains_sum: f64,
+    // The gain applied to the previous frame, and the factor to restore it to the target gain.
+    gain: f64,
+    release_factor: f64,
+    time: usize,
+}
+
+impl PeakLimiter {
+    fn new(
+        threshold: f64,
+        lookahead: usize,
+        release: usize,
+        channels: usize,
+    ) -> Result<Self, MixerError> {
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            return Err(MixerError::InvalidLimiterThreshold { threshold });
+        }
+        let window = lookahead + 1;
+        Ok(Self {
+            threshold,
+            delay: vec![vec![0.0; channels]; lookahead],
+            minimums: VecDeque::with_capacity(window),
+            gains: vec![1.0; window],
+            gains_sum: window as f64,
+            gain: 1.0,
+            // The gain is restored by a one-pole filter whose time constant is the release.
+            release_factor: if release == 0 {
+                1.0
+            } else {
+                1.0 - (-1.0 / release as f64).exp()
+            },
+            time: 0,
+        })
+    }
+
+    fn process(&mut self, frame: &mut [f64]) {
+        let window = self.gains.len();
+        let peak = frame
+            .iter()
+            .fold(0.0_f64, |peak, data| peak.max(data.abs()));
+        let required = if peak > self.threshold {
+            self.threshold / peak
+        } else {
+            1.0
+        };
+
+        // Update the minimum of g in [time - L, time].
+        while let Some((_, gain)) = self.minimums.back() {
+            if *gain < required {
+                break;
+            }
+            self.minimums.pop_back();
+        }
+        self.minimums.push_back((self.time, required));
+        while let Some((time, _)) = self.minimums.front() {
+            if time + window > self.time {
+                break;
+            }
+            self.minimums.pop_front();
+        }
+        let minimum = self.minimums.front().expect("minimum must exist").1;
+
+        // Update the average of m in [time - L, time].
+        let index = self.time % window;
+        self.gains_sum += minimum - self.gains[index];
+        self.gains[index] = minimum;
+        let target = self.gains_sum / window as f64;
+        self.gain = if target < self.gain {
+            target
+        } else {
+            self.gain + (target - self.gain) * self.release_factor
+        };
+
+        // Output the frame delayed by L frames and store the current frame. The output is clamped
+        // in case the accumulated rounding errors of the sum make the gain slightly too large.
+        let lookahead = self.delay.len();
+        for (channel, data) in frame.iter_mut().enumerate() {
+            let delayed = match self.delay.get_mut(self.time % lookahead.max(1)) {
+                Some(delayed) => std::mem::replace(&mut delayed[channel], *data),
+                None => *data,
+            };
+            *data = (delayed * self.gain).clamp(-self.threshold, self.threshold);
+        }
+        self.time += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clip() {
+        let mut protector = Protector::new(OutputProtection::HardClip, 4)
+            .unwrap()
+            .unwrap();
+        protector
+            .frame_mut()
+            .copy_from_slice(&[0.5, -0.99, 1.5, -3.0]);
+        protector.process();
+        assert_eq!(protector.frame_mut(), &[0.5, -0.99, 1.0, -1.0]);
+        assert_eq!(protector.latency(), 0);
+
+        let mut protector = Protector::new(OutputProtection::SoftClip, 4)
+            .unwrap()
+            .unwrap();
+        protector
+            .frame_mut()
+            .copy_from_slice(&[0.25, -0.5, 0.75, -4.0]);
+        protector.process();
+        let frame = protector.frame_mut();
+        assert_eq!(frame[..2], [0.25, -0.5]);
+        assert!(frame[2] > 0.5 && frame[2] < 0.75);
+        assert!(frame[3] < -0.99 && frame[3] > -1.0);
+
+        // The soft clip is monotonic.
+        let mut previous = soft_clip(-10.0);
+        for i in -1000..=1000 {
+            let data = soft_clip(f64::from(i) * 0.01);
+            assert!(data >= previous);
+            previous = data;
+        }
+
+        assert!(Protector::new(OutputProtection::None, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_limiter() {
+        let lookahead = 16;
+        let protection = OutputProtection::Limiter {
+            threshold: 0.9,
+            lookahead,
+            release: 64,
+        };
+        let mut protector = Protector::new(protection, 2).unwrap().unwrap();
+        assert_eq!(protector.latency(), lookahead);
+
+        // A sine wave whose amplitude goes from 0.5 to 2.0 and back to 0.5.
+        let input: Vec<f64> = (0..4096)
+            .map(|t| {
+                let amplitude = if (1024..2048).contains(&t) { 2.0 } else { 0.5 };
+                amplitude * (t as f64 * 0.05).sin()
+            })
+            .collect();
+        let mut output = Vec::new();
+        for data in &input {
+            let frame = protector.frame_mut();
+            frame[0] = *data;
+            frame[1] = -*data;
+            protector.process();
+            let frame = protector.frame_mut();
+            assert_eq!(frame[0], -frame[1]);
+            output.push(frame[0]);
+        }
+
+        // The output is delayed, limited, and unchanged before the loud part arrives and long
+        // after it ends.
+        assert!(output[..lookahead].iter().all(|data| *data == 0.0));
+        assert!(output.iter().all(|data| data.abs() <= 0.9));
+        assert_eq!(output[lookahead..1024], input[..1024 - lookahead]);
+        assert!(output[3500..]
+            .iter()
+            .zip(input[3500 - lookahead..].iter())
+            .all(|(output, input)| (output - input).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_limiter_with_invalid_threshold() {
+        for &threshold in [0.0, -0.5, 1.5, f64::NAN].iter() {
+            let protection = OutputProtection::Limiter {
+                threshold,
+                lookahead: 16,
+                release: 64,
+            };
+            match Protector::new(protection, 2) {
+                Err(MixerError::InvalidLimiterThreshold { .. }) => {}
+                _ => panic!("threshold {} must be rejected", threshold),
+            }
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use crate::channel::Channel;
+use crate::coefficient::{Coefficient, MixerError, MixingCoefficient, Side};
+use crate::config::MixerConfig;
+use crate::sample::Sample;
+use crate::{check_frames, interleaved_frames, InputFrame};
+use std::f64::consts::FRAC_PI_2;
+use std::marker::PhantomData;
+
+// The curve for fading out the previous matrix and fading in the new one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RampCurve {
+    // The coefficients are interpolated linearly, so the level of the correlated data, e.g.,
+    // the same data in different channels, is kept.
+    Linear,
+    // The previous matrix is faded out by cos and the new one is faded in by sin, so the power
+    // of the uncorrelated data is kept.
+    EqualPower,
+}
+
+// A mixer that changes its mixing matrix and gain smoothly. When a new matrix or gain is set,
+// the coefficients are interpolated from the current ones to the new ones over the ramp frames
+// in the following mixed frames, instead of jumping to the new ones, which causes clicks. The
+// gain is always interpolated linearly. If a new matrix or gain is set during a ramp, the new
+// ramp starts from the coefficients at that moment.
+//
+// The mixer is stateful, so it must be used for only one continuous stream. Unlike MixerStream,
+// the dither and the output protection in the config are not applied.
+#[derive(Debug)]
+pub struct RampingMixer<I, O = I>
+where
+    I: Sample,
+    O: Sample,
+{
+    // The coefficient ramped to, and the one ramped from when the matrix is being changed.
+    coefficient: Coefficient<O::Native>,
+    previous_coefficient: Option<Coefficient<O::Native>>,
+    gain: f64,
+    previous_gain: f64,
+    curve: RampCurve,
+    ramp_frames: usize,
+    // The number of the frames mixed in the current ramp.
+    position: usize,
+    input_type: PhantomData<I>,
+}
+
+impl<I, O> RampingMixer<I, O>
+where
+    I: Sample,
+    O: Sample,
+{
+    // Create a mixer ramping the changes over the given number of frames. The changes are
+    // applied immediately if ramp_frames is 0.
+    pub fn new(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        ramp_frames: usize,
+        curve: RampCurve,
+    ) -> Result<Self, MixerError> {
+        Self::with_config(
+            input_channels,
+            output_channels,
+            &MixerConfig::default(),
+            ramp_frames,
+            curve,
+        )
+    }
+
+    // Create a mixer whose initial matrix is generated with the levels in the given config.
+    pub fn with_config(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        config: &MixerConfig,
+        ramp_frames: usize,
+        curve: RampCurve,
+    ) -> Result<Self, MixerError> {
+        Ok(Self {
+            coefficient: Coefficient::create(input_channels, output_channels, config)?,
+            previous_coefficient: None,
+            gain: 1.0,
+            previous_gain: 1.0,
+            curve,
+            ramp_frames,
+            position: 0,
+            input_type: PhantomData,
+        })
+    }
+
+    // Ramp to the matrix generated with the levels in the given config.
+    pub fn set_config(&mut self, config: &MixerConfig) -> Result<(), MixerError> {
+        let coefficient =
+            Coefficient::create(self.input_channels(), self.output_channels(), config)?;
+        self.ramp_to(coefficient);
+        Ok(())
+    }
+
+    // Ramp to the given NxM matrix, where matrix[i][j] is the coefficient for mixing
+    // input-channel j to output-channel i. See Mixer::from_matrix for how it's used.
+    pub fn set_matrix(&mut self, matrix: &[Vec<f64>]) -> Result<(), MixerError> {
+        let coefficient =
+            Coefficient::create_with_matrix(self.input_channels(), self.output_channels(), matrix)?;
+        self.ramp_to(coefficient);
+        Ok(())
+    }
+
+    // Ramp to the given linear gain, which is applied to all the mixed data.
+    pub fn set_gain(&mut self, gain: f64) {
+        assert!(gain.is_finite(), "gain must be a finite number.");
+        self.settle();
+        self.gain = gain;
+        if self.ramp_frames == 0 {
+            self.previous_gain = gain;
+        }
+        self.position = 0;
+    }
+
+    // Return the gain ramped to.
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    // Return the matrix ramped to. See Mixer::matrix.
+    pub fn matrix(&self) -> Vec<Vec<f64>> {
+        self.coefficient.normalized_matrix().to_vec()
+    }
+
+    pub fn is_ramping(&self) -> bool {
+        self.position < self.ramp_frames
+            && (self.previous_coefficient.is_some() || self.previous_gain != self.gain)
+    }
+
+    pub fn input_channels(&self) -> &[Channel] {
+        self.coefficient.input_channels()
+    }
+
+    pub fn output_channels(&self) -> &[Channel] {
+        self.coefficient.output_channels()
+    }
+
+    // Mix all the interleaved frames in input buffer to output buffer and return the number of
+    // mixed frames, advancing the ramp by the number of mixed frames. See Mixer::mix_interleaved
+    // for the buffers.
+    pub fn mix_interleaved(&mut self, input_buffer: &[I], output_buffer: &mut [O]) -> usize {
+        let input_channels = self.input_channels().len();
+        let output_channels = self.output_channels().len();
+        let frames = interleaved_frames(input_buffer, input_channels, Side::Input);
+        check_frames(
+            frames,
+            interleaved_frames(output_buffer, output_channels, Side::Output),
+        );
+        let mut frame = InputFrame::<I, O>::new(input_channels);
+        let would_overflow =
+            frame.would_overflow(self.coefficient.would_overflow_from_coefficient_value());
+        for (input, output) in input_buffer
+            .chunks_exact(input_channels)
+            .zip(output_buffer.chunks_exact_mut(output_channels))
+        {
+            frame.read(|j| input[j]);
+            if !self.is_ramping() {
+                for (i, data) in output.iter_mut().enumerate() {
+                    let value = frame.mix(&self.coefficient, i, |j| input[j]);
+                    *data = O::from_native(if self.gain == 1.0 {
+                        O::Native::from_coefficient_value(value, would_overflow)
+                    } else {
+                        O::Native::from_mixed_f64(
+                            self.gain * O::Native::coefficient_value_to_f64(value),
+                        )
+                    });
+                }
+                continue;
+            }
+
+            // The weights of the frame in the ramp, so the new ones are reached at the last frame.
+            self.position += 1;
+            let (previous_weight, weight) = self.weights();
+            let gain = self.gain_at_position();
+            for (i, data) in output.iter_mut().enumerate() {
+                let value = frame.mix(&self.coefficient, i, |j| input[j]);
+                let mut mixed = O::Native::coefficient_value_to_f64(value);
+                if let Some(previous) = &self.previous_coefficient {
+                    let previous_value = frame.mix(previous, i, |j| input[j]);
+                    mixed = previous_weight * O::Native::coefficient_value_to_f64(previous_value)
+                        + weight * mixed;
+                }
+                *data = O::from_native(O::Native::from_mixed_f64(gain * mixed));
+            }
+            if !self.is_ramping() {
+                self.previous_coefficient = None;
+                self.previous_gain = self.gain;
+            }
+        }
+        frames
+    }
+
+    fn ramp_to(&mut self, coefficient: Coefficient<O::Native>) {
+        self.settle();
+        let current = std::mem::replace(&mut self.coefficient, coefficient);
+        // There is nothing to ramp from if the changes are applied immediately.
+        if self.ramp_frames > 0 {
+            self.previous_coefficient = Some(self.previous_coefficient.take().unwrap_or(current));
+        }
+        self.position = 0;
+    }
+
+    // Make the coefficients and gain at the current position of the ramp the ones to ramp from,
+    // so a new ramp can start from there.
+    fn settle(&mut self) {
+        if self.is_ramping() {
+            if let Some(previous) = &self.previous_coefficient {
+                let (previous_weight, weight) = self.weights();
+                self.previous_coefficient = Some(Coefficient::interpolate(
+                    previous,
+                    &self.coefficient,
+                    previous_weight,
+                    weight,
+                ));
+            }
+            self.previous_gain = self.gain_at_position();
+        } else {
+            self.previous_coefficient = None;
+            self.previous_gain = self.gain;
+        }
+    }
+
+    // Return the weights of the previous and new coefficients at the current position.
+    fn weights(&self) -> (f64, f64) {
+        let progress = self.position as f64 / self.ramp_frames as f64;
+        match self.curve {
+            RampCurve::Linear => (1.0 - progress, progress),
+            RampCurve::EqualPower => ((progress * FRAC_PI_2).cos(), (progress * FRAC_PI_2).sin()),
+        }
+    }
+
+    fn gain_at_position(&self) -> f64 {
+        let progress = self.position as f64 / self.ramp_frames as f64;
+        self.previous_gain + (self.gain - self.previous_gain) * progress
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::channel::ChannelLayout;
+    use crate::Mixer;
+
+    #[test]
+    fn test_ramp_gain() {
+        let channels = ChannelLayout::stereo();
+        let mut mixer =
+            RampingMixer::<f32>::new(&channels, &channels, 4, RampCurve::Linear).unwrap();
+        assert!(!mixer.is_ramping());
+        mixer.set_gain(0.0);
+        assert!(mixer.is_ramping());
+        assert_eq!(mixer.gain(), 0.0);
+
+        // The gain ramps linearly across the mixing calls, and the equal-power curve is not
+        // used when only the gain changes.
+        let input = [1.0, -1.0, 1.0, -1.0, 1.0, -1.0];
+        let mut output = [0.0; 6];
+        assert_eq!(mixer.mix_interleaved(&input, &mut output), 3);
+        assert_eq!(output, [0.75, -0.75, 0.5, -0.5, 0.25, -0.25]);
+        mixer.mix_interleaved(&input, &mut output);
+        assert_eq!(output, [0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(!mixer.is_ramping());
+
+        // A new gain during a ramp ramps from the current gain.
+        mixer.set_gain(1.0);
+        mixer.mix_interleaved(&input[..2], &mut output[..2]);
+        assert_eq!(output[..2], [0.25, -0.25]);
+        mixer.set_gain(0.25);
+        mixer.mix_interleaved(&input, &mut output);
+        assert_eq!(output[..2], [0.25, -0.25]);
+        assert_eq!(output[4..], [0.25, -0.25]);
+    }
+
+    #[test]
+    fn test_ramp_matrix() {
+        let input_channels = [
+            Channel::FrontLeft,
+            Channel::FrontRight,
+            Channel::FrontCenter,
+        ];
+        let output_channels = ChannelLayout::stereo();
+        let config = MixerConfig::default().with_center_mix_level(0.0);
+        let new_config = MixerConfig::default().with_center_mix_level(1.0);
+        let ramp_frames = 8;
+        let input: Vec<f32> = [0.25, -0.25, 0.5].repeat(ramp_frames * 2);
+
+        let mix = |config: &MixerConfig| {
+            let mut output = [0.0; 2];
+            Mixer::<f32>::with_config(&input_channels, &output_channels, config)
+                .unwrap()
+                .mix(&input[..3], &mut output);
+            output
+        };
+        let (start, end) = (mix(&config), mix(&new_config));
+
+        for curve in [RampCurve::Linear, RampCurve::EqualPower].iter() {
+            let mut mixer = RampingMixer::<f32>::with_config(
+                &input_channels,
+                &output_channels,
+                &config,
+                ramp_frames,
+                *curve,
+            )
+            .unwrap();
+            mixer.set_config(&new_config).unwrap();
+            assert_eq!(
+                mixer.matrix(),
+                Mixer::<f32>::with_config(&input_channels, &output_channels, &new_config)
+                    .unwrap()
+                    .matrix()
+            );
+            let mut output = vec![0.0; ramp_frames * 4];
+            mixer.mix_interleaved(&input, &mut output);
+
+            // The output moves from the start to the end monotonically for the linear curve.
+            // For the equal-power one, it's louder than the linear one in the middle.
+            let middle = output[(ramp_frames / 2 - 1) * 2];
+            let linear_middle = (start[0] + end[0]) / 2.0;
+            match curve {
+                RampCurve::Linear => assert!((middle - linear_middle).abs() < 1e-6),
+                RampCurve::EqualPower => assert!(middle > linear_middle),
+            }
+            for frame in output.chunks(2).skip(ramp_frames - 1) {
+                assert_eq!(frame, end);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ramp_without_frames() {
+        // The changes are applied immediately, so the output is the same as the one of Mixer.
+        let input_channels = ChannelLayout::surround_5_1();
+        let output_channels = ChannelLayout::stereo();
+        let mut mixer =
+            RampingMixer::<i16>::new(&input_channels, &output_channels, 0, RampCurve::Linear)
+                .unwrap();
+        let matrix = vec![
+            vec![1.0, 0.0, 0.5, 0.0, 0.25, 0.0],
+            vec![0.0, 1.0, 0.5, 0.0, 0.0, 0.25],
+        ];
+        mixer.set_matrix(&matrix).unwrap();
+        assert!(!mixer.is_ramping());
+        assert!(mixer.previous_coefficient.is_none());
+
+        let input: Vec<i16> = (0..16 * 6).map(|i| (i * 997) as i16).collect();
+        let mut output = vec![0; 16 * 2];
+        mixer.mix_interleaved(&input, &mut output);
+        let target = Mixer::<i16>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+        let mut expected = vec![0; 16 * 2];
+        target.mix_interleaved(&input, &mut expected);
+        assert_eq!(output, expected);
+
+        mixer.set_gain(0.5);
+        assert!(!mixer.is_ramping());
+        assert_eq!(mixer.previous_gain, 0.5);
+        mixer.set_gain(1.0);
+        mixer.mix_interleaved(&input, &mut output);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_ramp_matrix_i16() {
+        let input_channels = ChannelLayout::surround_5_1();
+        let output_channels = ChannelLayout::stereo();
+        let mut mixer =
+            RampingMixer::<i16>::new(&input_channels, &output_channels, 16, RampCurve::Linear)
+                .unwrap();
+        let matrix = vec![
+            vec![1.0, 0.0, 0.5, 0.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.5, 0.0, 0.0, 0.0],
+        ];
+        mixer.set_matrix(&matrix).unwrap();
+        assert_eq!(
+            mixer.set_matrix(&matrix[..1]),
+            Err(MixerError::MatrixSizeMismatch {
+                side: Side::Output,
+                expected: 2,
+                actual: 1,
+            })
+        );
+
+        // The ramp is continuous even if it's restarted, and the output after the ramp is the
+        // same as the one of Mixer.
+        let input: Vec<i16> = (0..64 * 6).map(|i| ((i % 6) * 2000) as i16).collect();
+        let mut output = vec![0; 64 * 2];
+        mixer.mix_interleaved(&input[..8 * 6], &mut output[..8 * 2]);
+        mixer.set_gain(0.5);
+        mixer.mix_interleaved(&input[8 * 6..], &mut output[8 * 2..]);
+        for pair in output.chunks(2).collect::<Vec<_>>().windows(2) {
+            assert!((i32::from(pair[0][0]) - i32::from(pair[1][0])).abs() < 600);
+        }
+
+        let target = Mixer::<i16>::from_matrix(&input_channels, &output_channels, &matrix).unwrap();
+        let mut expected = [0; 2];
+        target.mix(&input[..6], &mut expected);
+        assert!((f64::from(output[126]) - f64::from(expected[0]) * 0.5).abs() <= 1.0);
+        assert_eq!(mixer.matrix(), target.matrix());
+
+        mixer.set_gain(1.0);
+        let mut output = vec![0; 64 * 2];
+        mixer.mix_interleaved(&input, &mut output);
+        assert_eq!(output[126..], expected);
+    }
+}
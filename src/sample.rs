@@ -0,0 +1,174 @@
+use crate::coefficient::MixingCoefficient;
+use std::fmt::Debug;
+
+// The audio sample types that can be mixed by the Mixer. The natively supported types are f32,
+// f64, i16, i32, u8, I24 and I24In32, whose mixing arithmetic is implemented in the crate. A
+// custom sample type can be mixed by implementing this trait, with a natively supported type
+// as its Native type and the conversions between them. The mixer converts the input data to
+// the Native type before mixing and converts the mixed data from the Native type afterwards,
+// so the Native type should be able to hold the sample values without losing precision.
+pub trait Sample: Copy + Debug {
+    type Native: MixingCoefficient;
+
+    fn to_native(value: Self) -> Self::Native;
+    fn from_native(value: Self::Native) -> Self;
+
+    // Return the samples as Native samples without copying, if Self has the same memory layout
+    // as Self::Native, so the buffers can be mixed by the optimized kernels in one go. It returns
+    // None by default, and then the samples are converted one by one.
+    fn as_native_slice(_values: &[Self]) -> Option<&[Self::Native]> {
+        None
+    }
+    fn as_native_slice_mut(_values: &mut [Self]) -> Option<&mut [Self::Native]> {
+        None
+    }
+}
+
+macro_rules! impl_native_sample {
+    ($($t:ty),*) => {
+        $(
+            impl Sample for $t {
+                type Native = Self;
+
+                fn to_native(value: Self) -> Self::Native {
+                    value
+                }
+
+                fn from_native(value: Self::Native) -> Self {
+                    value
+                }
+
+                fn as_native_slice(values: &[Self]) -> Option<&[Self::Native]> {
+                    Some(values)
+                }
+
+                fn as_native_slice_mut(values: &mut [Self]) -> Option<&mut [Self::Native]> {
+                    Some(values)
+                }
+            }
+        )*
+    };
+}
+
+impl_native_sample!(f32, f64, i16, i32, u8, I24, I24In32);
+
+// The sample types that can't be represented by the primitive types.
+
+// A signed 24-bit integer sample packed in 3 bytes in little-endian order, i.e., S24_3LE.
+// Since it's a transparent wrapper of [u8; 3], the audio data in bytes can be viewed as
+// I24 samples without copying.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(transparent)]
+pub struct I24([u8; 3]);
+
+impl I24 {
+    pub const MAX: i32 = 0x7F_FFFF;
+    pub const MIN: i32 = -0x80_0000;
+
+    // Panic if the value is out of the 24-bit range.
+    pub fn new(value: i32) -> Self {
+        assert!(
+            (Self::MIN..=Self::MAX).contains(&value),
+            "value is out of the 24-bit range."
+        );
+        let bytes = value.to_le_bytes();
+        Self([bytes[0], bytes[1], bytes[2]])
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 3]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 3] {
+        self.0
+    }
+
+    // Return the sign-extended value.
+    pub fn to_i32(self) -> i32 {
+        i32::from_le_bytes([0, self.0[0], self.0[1], self.0[2]]) >> 8
+    }
+}
+
+// A signed 24-bit integer sample stored in the low 3 bytes of a 32-bit container, i.e., S24_LE.
+// The high byte of the container is ignored when reading or comparing the samples, and it's
+// filled with the sign bit when writing the sample. Since it's a transparent wrapper of i32, the
+// audio data in 32-bit containers can be viewed as I24In32 samples without copying.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(transparent)]
+pub struct I24In32(i32);
+
+impl I24In32 {
+    pub const MAX: i32 = I24::MAX;
+    pub const MIN: i32 = I24::MIN;
+
+    // Panic if the value is out of the 24-bit range.
+    pub fn new(value: i32) -> Self {
+        assert!(
+            (Self::MIN..=Self::MAX).contains(&value),
+            "value is out of the 24-bit range."
+        );
+        Self(value)
+    }
+
+    // Use the low 3 bytes of the container as the sample, no matter what the high byte is.
+    pub fn from_container(container: i32) -> Self {
+        Self(container)
+    }
+
+    pub fn to_container(self) -> i32 {
+        self.0
+    }
+
+    // Return the sign-extended value.
+    pub fn to_i32(self) -> i32 {
+        (self.0 << 8) >> 8
+    }
+}
+
+// The samples are equal if their low 3 bytes are equal, no matter what the high bytes are.
+impl PartialEq for I24In32 {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_i32() == other.to_i32()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_i24() {
+        for value in &[0, 1, -1, 0x12_3456, -0x12_3456, I24::MAX, I24::MIN] {
+            let sample = I24::new(*value);
+            assert_eq!(sample.to_i32(), *value);
+            assert_eq!(I24::from_le_bytes(sample.to_le_bytes()), sample);
+        }
+        assert_eq!(I24::new(0x12_3456).to_le_bytes(), [0x56, 0x34, 0x12]);
+        assert_eq!(I24::from_le_bytes([0xFF, 0xFF, 0xFF]).to_i32(), -1);
+        assert_eq!(I24::from_le_bytes([0x00, 0x00, 0x80]).to_i32(), I24::MIN);
+    }
+
+    #[test]
+    fn test_i24_in_32() {
+        for value in &[0, 1, -1, 0x12_3456, -0x12_3456, I24In32::MAX, I24In32::MIN] {
+            let sample = I24In32::new(*value);
+            assert_eq!(sample.to_i32(), *value);
+            assert_eq!(sample.to_container(), *value);
+        }
+        // The high byte is ignored.
+        assert_eq!(I24In32::from_container(0x00FF_FFFF).to_i32(), -1);
+        assert_eq!(I24In32::from_container(0x7F80_0000).to_i32(), I24In32::MIN);
+        assert_eq!(I24In32::from_container(-0x0100_0000 + 1).to_i32(), 1);
+        assert_eq!(I24In32::from_container(0x00FF_FFFF), I24In32::new(-1));
+        assert_ne!(
+            I24In32::from_container(0x00FF_FFFF),
+            I24In32::new(0x7F_FFFF)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_i24_out_of_range() {
+        let _ = I24::new(I24::MAX + 1);
+    }
+}
@@ -0,0 +1,709 @@
+use crate::coefficient::MixingCoefficient;
+use crate::sample::Sample;
+use std::convert::TryFrom;
+
+// The kernels mixing the interleaved frames with the SIMD instructions. The output buffer is
+// viewed as a flat sequence of N-channel frames and split into chunks of W lanes, where W is 8
+// for AVX2 and 4 for SSE2. A chunk may cover several frames, or part of a frame, so the data of
+// each lane is read from its own frame and mixed with the coefficients of its own output
+// channel. The layout of the lanes repeats every lcm(N, W) output samples, so the input offsets
+// and coefficients of the lanes are computed once, for the lcm(N, W) / W phases of the chunks.
+//
+// The kernels produce exactly the same output as the scalar code. The f32 products are summed
+// in the order of the input channels without fused multiply-add, and the i16 products are
+// summed in i32, which never overflows since the absolute row sums of the matrix are limited.
+// The f32 samples multiplied by zero coefficients are masked out, since the execution plan
+// skips the zero coefficients, so a NaN or infinite sample in an input channel that an output
+// channel doesn't use never reaches that output channel.
+
+// The instruction set used by the kernel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    // It's only detected on the other architectures, since SSE2 is always available on x86_64.
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+impl Level {
+    // The best level supported by the running CPU.
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Level::Avx2;
+            }
+            // SSE2 is part of the x86_64 baseline.
+            Level::Sse2
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            Level::Scalar
+        }
+    }
+
+    fn lanes(self) -> usize {
+        match self {
+            Level::Scalar => 1,
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse2 => 4,
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx2 => 8,
+        }
+    }
+}
+
+const MAX_LANES: usize = 8;
+
+// The kernel for the sample types of a mixer.
+#[derive(Debug)]
+pub enum Kernel {
+    F32(F32Kernel),
+    I16(I16Kernel),
+}
+
+impl Kernel {
+    // Create the kernel mixing I to O with the normalized NxM matrix. Return None if I and O
+    // can't be viewed as the samples of the same kernel.
+    pub fn create<I: Sample, O: Sample>(
+        matrix: &[Vec<f64>],
+        would_overflow: Option<bool>,
+    ) -> Option<Self> {
+        if is_f32::<I>() && is_f32::<O>() {
+            Some(Kernel::F32(F32Kernel::new(matrix)))
+        } else if is_i16::<I>() && is_i16::<O>() {
+            let would_overflow = would_overflow.expect("would_overflow must have value for i16");
+            Some(Kernel::I16(I16Kernel::new(matrix, would_overflow)))
+        } else {
+            None
+        }
+    }
+
+    // Mix the interleaved frames, which must have been validated to contain the same number of
+    // frames. Return false if the buffers can't be viewed as the samples of the kernel.
+    pub fn mix<I: Sample, O: Sample>(&self, input: &[I], output: &mut [O]) -> bool {
+        match self {
+            Kernel::F32(kernel) => {
+                let input = I::as_native_slice(input).and_then(I::Native::as_f32_slice);
+                let output = O::as_native_slice_mut(output).and_then(O::Native::as_f32_slice_mut);
+                match (input, output) {
+                    (Some(input), Some(output)) => kernel.mix(input, output),
+                    _ => return false,
+                }
+            }
+            Kernel::I16(kernel) => {
+                let input = I::as_native_slice(input).and_then(I::Native::as_i16_slice);
+                let output = O::as_native_slice_mut(output).and_then(O::Native::as_i16_slice_mut);
+                match (input, output) {
+                    (Some(input), Some(output)) => kernel.mix(input, output),
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+}
+
+// The types are checked with empty slices since they are only known by their hooks.
+fn is_f32<S: Sample>() -> bool {
+    S::as_native_slice(&[])
+        .and_then(S::Native::as_f32_slice)
+        .is_some()
+}
+
+fn is_i16<S: Sample>() -> bool {
+    S::as_native_slice(&[])
+        .and_then(S::Native::as_i16_slice)
+        .is_some()
+}
+
+// The layout of the lanes in the chunks of the output samples.
+#[derive(Debug)]
+struct Cycle {
+    lanes: usize,
+    input_channels: usize,
+    output_channels: usize,
+    phases: usize,
+    // The number of frames in lcm(N, W) output samples.
+    frames_per_cycle: usize,
+}
+
+impl Cycle {
+    fn new(input_channels: usize, output_channels: usize, lanes: usize) -> Self {
+        let samples = lcm(output_channels, lanes);
+        Self {
+            lanes,
+            input_channels,
+            output_channels,
+            phases: samples / lanes,
+            frames_per_cycle: samples / output_channels,
+        }
+    }
+
+    // Return the phase of the chunk and the index of the first input sample of its cycle.
+    fn chunk(&self, chunk: usize) -> (usize, usize) {
+        (
+            chunk % self.phases,
+            chunk / self.phases * self.frames_per_cycle * self.input_channels,
+        )
+    }
+}
+
+// The input offsets and coefficients of the lanes in a chunk, for every input channel, or every
+// pair of input channels for i16. The offsets are relative to the first frame of the cycle.
+#[derive(Debug)]
+struct Phase<C> {
+    offsets: Vec<[i32; MAX_LANES]>,
+    coefficients: Vec<C>,
+}
+
+// Create the phases of the cycle with the given number of groups of input channels. The
+// coefficients of a lane for group k are set by set_lane(coefficients, lane, k, i), which returns
+// the offset of group k in the frame, where i is the output channel of the lane.
+fn create_phases<C, F>(cycle: &Cycle, groups: usize, set_lane: F) -> Vec<Phase<C>>
+where
+    C: Clone + Default,
+    F: Fn(&mut C, usize, usize, usize) -> usize,
+{
+    if cycle.lanes == 1 {
+        return Vec::new();
+    }
+    (0..cycle.phases)
+        .map(|phase| {
+            let mut offsets = vec![[0; MAX_LANES]; groups];
+            let mut coefficients = vec![C::default(); groups];
+            for lane in 0..cycle.lanes {
+                let position = phase * cycle.lanes + lane;
+                let frame = position / cycle.output_channels;
+                let i = position % cycle.output_channels;
+                for (k, (offsets, coefficients)) in
+                    offsets.iter_mut().zip(&mut coefficients).enumerate()
+                {
+                    let offset = frame * cycle.input_channels + set_lane(coefficients, lane, k, i);
+                    offsets[lane] = i32::try_from(offset).expect("Too many channels");
+                }
+            }
+            Phase {
+                offsets,
+                coefficients,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct F32Kernel {
+    level: Level,
+    cycle: Cycle,
+    // The NxM matrix in row-major order, used by the scalar code and the remaining samples.
+    matrix: Vec<f32>,
+    phases: Vec<Phase<[f32; MAX_LANES]>>,
+}
+
+impl F32Kernel {
+    // Create a kernel for the NxM matrix with the best instruction set of the running CPU.
+    pub fn new(matrix: &[Vec<f64>]) -> Self {
+        Self::with_level(matrix, Level::detect())
+    }
+
+    pub fn with_level(matrix: &[Vec<f64>], level: Level) -> Self {
+        let (m, n) = matrix_size(matrix);
+        let flat: Vec<f32> = matrix
+            .iter()
+            .flat_map(|row| row.iter().map(|coef| *coef as f32))
+            .collect();
+        let cycle = Cycle::new(m, n, level.lanes());
+        let phases = create_phases(
+            &cycle,
+            m,
+            |coefficients: &mut [f32; MAX_LANES], lane, j, i| {
+                coefficients[lane] = flat[i * m + j];
+                j
+            },
+        );
+        Self {
+            level,
+            cycle,
+            matrix: flat,
+            phases,
+        }
+    }
+
+    // Mix the interleaved input frames to the interleaved output frames. The buffers must have
+    // been validated to contain the same number of frames.
+    pub fn mix(&self, input: &[f32], output: &mut [f32]) {
+        let (m, n) = (self.cycle.input_channels, self.cycle.output_channels);
+        let frames = input.len() / m;
+        assert_eq!(input.len(), frames * m);
+        assert_eq!(output.len(), frames * n);
+        let lanes = self.level.lanes();
+        let chunks = if lanes == 1 { 0 } else { output.len() / lanes };
+        match self.level {
+            Level::Scalar => {}
+            // Safety: The CPU features are detected when the level is chosen, and the offsets of
+            // the chunks are within the input frames since the chunks are within the output.
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse2 => unsafe { self.mix_sse2(input, output, chunks) },
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx2 => unsafe { self.mix_avx2(input, output, chunks) },
+        }
+        self.mix_scalar(input, output, chunks * lanes);
+    }
+
+    // Mix the output samples from the given position to the end.
+    fn mix_scalar(&self, input: &[f32], output: &mut [f32], start: usize) {
+        let (m, n) = (self.cycle.input_channels, self.cycle.output_channels);
+        for (position, data) in output.iter_mut().enumerate().skip(start) {
+            let (frame, i) = (position / n, position % n);
+            let row = &self.matrix[i * m..(i + 1) * m];
+            let mut value = 0.0;
+            for (coef, input) in row.iter().zip(&input[frame * m..(frame + 1) * m]) {
+                if *coef != 0.0 {
+                    value += coef * input;
+                }
+            }
+            *data = value;
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    unsafe fn mix_sse2(&self, input: &[f32], output: &mut [f32], chunks: usize) {
+        use std::arch::x86_64::*;
+        for chunk in 0..chunks {
+            let (phase, base) = self.cycle.chunk(chunk);
+            let phase = &self.phases[phase];
+            let frames = input.as_ptr().add(base);
+            let zero = _mm_setzero_ps();
+            let mut sum = zero;
+            for (offsets, coefficients) in phase.offsets.iter().zip(&phase.coefficients) {
+                let data = _mm_set_ps(
+                    *frames.add(offsets[3] as usize),
+                    *frames.add(offsets[2] as usize),
+                    *frames.add(offsets[1] as usize),
+                    *frames.add(offsets[0] as usize),
+                );
+                let coefficients = _mm_loadu_ps(coefficients.as_ptr());
+                let data = _mm_and_ps(data, _mm_cmpneq_ps(coefficients, zero));
+                sum = _mm_add_ps(sum, _mm_mul_ps(coefficients, data));
+            }
+            _mm_storeu_ps(output.as_mut_ptr().add(chunk * 4), sum);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn mix_avx2(&self, input: &[f32], output: &mut [f32], chunks: usize) {
+        use std::arch::x86_64::*;
+        for chunk in 0..chunks {
+            let (phase, base) = self.cycle.chunk(chunk);
+            let phase = &self.phases[phase];
+            let frames = input.as_ptr().add(base);
+            let zero = _mm256_setzero_ps();
+            let mut sum = zero;
+            for (offsets, coefficients) in phase.offsets.iter().zip(&phase.coefficients) {
+                let offsets = _mm256_loadu_si256(offsets.as_ptr() as *const __m256i);
+                let data = _mm256_i32gather_ps(frames, offsets, 4);
+                let coefficients = _mm256_loadu_ps(coefficients.as_ptr());
+                let data = _mm256_and_ps(data, _mm256_cmp_ps(coefficients, zero, _CMP_NEQ_UQ));
+                sum = _mm256_add_ps(sum, _mm256_mul_ps(coefficients, data));
+            }
+            _mm256_storeu_ps(output.as_mut_ptr().add(chunk * 8), sum);
+        }
+    }
+}
+
+// The i16 kernel reads the samples of two adjacent input channels as one 32-bit value and
+// multiplies them by their Q15 coefficients with the 16-bit pairwise multiply-add, summing the
+// products into 32-bit lanes. A Q15 coefficient can be up to 1.99 in magnitude, which doesn't
+// fit in 16 bits, so the pairs with such coefficients are multiplied twice, by the two halves of
+// the coefficients. The sums are rounded like i16::from_coefficient_value and packed into 16
+// bits with saturation, which is the same as the clipping there.
+#[derive(Debug)]
+pub struct I16Kernel {
+    level: Level,
+    cycle: Cycle,
+    // The NxM matrix in Q15 in row-major order, used by the scalar code and the remaining
+    // samples.
+    matrix: Vec<i32>,
+    would_overflow: bool,
+    phases: Vec<Phase<Vec<[i16; 2 * MAX_LANES]>>>,
+}
+
+impl I16Kernel {
+    // Create a kernel for the NxM matrix with the best instruction set of the running CPU.
+    pub fn new(matrix: &[Vec<f64>], would_overflow: bool) -> Self {
+        Self::with_level(matrix, would_overflow, Level::detect())
+    }
+
+    pub fn with_level(matrix: &[Vec<f64>], would_overflow: bool, level: Level) -> Self {
+        let (m, n) = matrix_size(matrix);
+        let flat: Vec<i32> = matrix
+            .iter()
+            .flat_map(|row| row.iter().copied().map(i16::coefficient_from_f64))
+            .collect();
+        let cycle = Cycle::new(m, n, level.lanes());
+        let phases = create_phases(
+            &cycle,
+            m / 2 + m % 2,
+            |coefficients: &mut [i32; 2 * MAX_LANES], lane, k, i| {
+                // The last pair of the odd input channels has no second channel, so the sample
+                // of the next frame read with it is multiplied by zero.
+                for (half, j) in [2 * k, 2 * k + 1].iter().enumerate() {
+                    coefficients[2 * lane + half] = if *j < m { flat[i * m + j] } else { 0 };
+                }
+                2 * k
+            },
+        )
+        .into_iter()
+        .map(|phase| Phase {
+            offsets: phase.offsets,
+            coefficients: phase.coefficients.iter().map(split_pairs).collect(),
+        })
+        .collect();
+        Self {
+            level,
+            cycle,
+            matrix: flat,
+            would_overflow,
+            phases,
+        }
+    }
+
+    // Mix the interleaved input frames to the interleaved output frames. The buffers must have
+    // been validated to contain the same number of frames.
+    pub fn mix(&self, input: &[i16], output: &mut [i16]) {
+        let (m, n) = (self.cycle.input_channels, self.cycle.output_channels);
+        let frames = input.len() / m;
+        assert_eq!(input.len(), frames * m);
+        assert_eq!(output.len(), frames * n);
+        let lanes = self.level.lanes();
+        // The last input channel of the odd input channels is read with the first channel of
+        // the next frame, so the last frame is mixed by the scalar code then.
+        let positions = if m % 2 == 1 {
+            frames.saturating_sub(1) * n
+        } else {
+            output.len()
+        };
+        let chunks = if lanes == 1 { 0 } else { positions / lanes };
+        match self.level {
+            Level::Scalar => {}
+            // Safety: The CPU features are detected when the level is chosen, and the pairs read
+            // by the chunks are within the input frames as described above.
+            #[cfg(target_arch = "x86_64")]
+            Level::Sse2 => unsafe { self.mix_sse2(input, output, chunks) },
+            #[cfg(target_arch = "x86_64")]
+            Level::Avx2 => unsafe { self.mix_avx2(input, output, chunks) },
+        }
+        self.mix_scalar(input, output, chunks * lanes);
+    }
+
+    // Mix the output samples from the given position to the end.
+    fn mix_scalar(&self, input: &[i16], output: &mut [i16], start: usize) {
+        let (m, n) = (self.cycle.input_channels, self.cycle.output_channels);
+        for (position, data) in output.iter_mut().enumerate().skip(start) {
+            let (frame, i) = (position / n, position % n);
+            let row = &self.matrix[i * m..(i + 1) * m];
+            let mut value = 0;
+            for (coef, input) in row.iter().zip(&input[frame * m..(frame + 1) * m]) {
+                value += coef * i32::from(*input);
+            }
+            *data = i16::from_coefficient_value(value, Some(self.would_overflow));
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    unsafe fn mix_sse2(&self, input: &[i16], output: &mut [i16], chunks: usize) {
+        use std::arch::x86_64::*;
+        for chunk in 0..chunks {
+            let (phase, base) = self.cycle.chunk(chunk);
+            let phase = &self.phases[phase];
+            let frames = input.as_ptr().add(base);
+            // The rounding offset of from_coefficient_value.
+            let mut sum = _mm_set1_epi32(1 << 14);
+            for (offsets, parts) in phase.offsets.iter().zip(&phase.coefficients) {
+                let data = _mm_set_epi32(
+                    read_pair(frames, offsets[3]),
+                    read_pair(frames, offsets[2]),
+                    read_pair(frames, offsets[1]),
+                    read_pair(frames, offsets[0]),
+                );
+                for part in parts {
+                    let coefficients = _mm_loadu_si128(part.as_ptr() as *const __m128i);
+                    sum = _mm_add_epi32(sum, _mm_madd_epi16(data, coefficients));
+                }
+            }
+            let sum = _mm_srai_epi32(sum, 15);
+            _mm_storel_epi64(
+                output.as_mut_ptr().add(chunk * 4) as *mut __m128i,
+                _mm_packs_epi32(sum, sum),
+            );
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn mix_avx2(&self, input: &[i16], output: &mut [i16], chunks: usize) {
+        use std::arch::x86_64::*;
+        for chunk in 0..chunks {
+            let (phase, base) = self.cycle.chunk(chunk);
+            let phase = &self.phases[phase];
+            let frames = input.as_ptr().add(base) as *const i32;
+            // The rounding offset of from_coefficient_value.
+            let mut sum = _mm256_set1_epi32(1 << 14);
+            for (offsets, parts) in phase.offsets.iter().zip(&phase.coefficients) {
+                // The offsets are in units of i16, so they are scaled by 2 bytes.
+                let offsets = _mm256_loadu_si256(offsets.as_ptr() as *const __m256i);
+                let data = _mm256_i32gather_epi32(frames, offsets, 2);
+                for part in parts {
+                    let coefficients = _mm256_loadu_si256(part.as_ptr() as *const __m256i);
+                    sum = _mm256_add_epi32(sum, _mm256_madd_epi16(data, coefficients));
+                }
+            }
+            let sum = _mm256_srai_epi32(sum, 15);
+            let packed = _mm_packs_epi32(
+                _mm256_castsi256_si128(sum),
+                _mm256_extracti128_si256(sum, 1),
+            );
+            _mm_storeu_si128(output.as_mut_ptr().add(chunk * 8) as *mut __m128i, packed);
+        }
+    }
+}
+
+// Convert the Q15 coefficients of the pairs of input channels in the lanes to 16 bits. They are
+// split into two halves if any of them doesn't fit in 16 bits. -32768 is excluded as well, since
+// the multiply-add of two -32768 * -32768 products overflows.
+fn split_pairs(coefficients: &[i32; 2 * MAX_LANES]) -> Vec<[i16; 2 * MAX_LANES]> {
+    let parts: Vec<Vec<i32>> = if coefficients
+        .iter()
+        .all(|coef| coef.abs() <= i32::from(i16::MAX))
+    {
+        vec![coefficients.to_vec()]
+    } else {
+        vec![
+            coefficients.iter().map(|coef| coef >> 1).collect(),
+            coefficients.iter().map(|coef| coef - (coef >> 1)).collect(),
+        ]
+    };
+    parts
+        .iter()
+        .map(|part| {
+            let mut converted = [0; 2 * MAX_LANES];
+            for (converted, coef) in converted.iter_mut().zip(part) {
+                *converted = i16::try_from(*coef).expect("Coefficient is out of range");
+            }
+            converted
+        })
+        .collect()
+}
+
+// Read the samples of a pair of input channels as a 32-bit value, with the first sample in the
+// low 16 bits.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+unsafe fn read_pair(frames: *const i16, offset: i32) -> i32 {
+    let pair = (frames.add(offset as usize) as *const [i16; 2]).read_unaligned();
+    i32::from(pair[0] as u16) | (i32::from(pair[1]) << 16)
+}
+
+// Return the numbers of the input and output channels of the NxM matrix.
+fn matrix_size(matrix: &[Vec<f64>]) -> (usize, usize) {
+    let n = matrix.len();
+    let m = matrix.first().map_or(0, |row| row.len());
+    assert!(m > 0 && n > 0);
+    (m, n)
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        let r = x % y;
+        x = y;
+        y = r;
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn levels() -> Vec<Level> {
+        let mut levels = vec![Level::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            levels.push(Level::Sse2);
+            if is_x86_feature_detected!("avx2") {
+                levels.push(Level::Avx2);
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn test_kernels() {
+        // All the channel counts, including the ones not dividing the lanes, and the frame
+        // counts leaving remaining samples.
+        for m in 1..=12 {
+            for n in 1..=12 {
+                let matrix: Vec<Vec<f64>> = (0..n)
+                    .map(|i| {
+                        (0..m)
+                            .map(|j| ((i * m + j) % 7) as f64 * 0.17 - 0.4)
+                            .collect()
+                    })
+                    .collect();
+                for &frames in [0, 1, 3, 17, 64].iter() {
+                    let input: Vec<f32> = (0..frames * m)
+                        .map(|k| ((k * 37) % 101) as f32 / 50.0 - 1.0)
+                        .collect();
+                    let mut expected = vec![0.0; frames * n];
+                    for f in 0..frames {
+                        for i in 0..n {
+                            let mut value = 0.0_f32;
+                            for j in 0..m {
+                                value += matrix[i][j] as f32 * input[f * m + j];
+                            }
+                            expected[f * n + i] = value;
+                        }
+                    }
+                    for level in levels() {
+                        let kernel = F32Kernel::with_level(&matrix, level);
+                        let mut output = vec![f32::NAN; frames * n];
+                        kernel.mix(&input, &mut output);
+                        assert_eq!(output, expected, "{:?} {}x{}", level, n, m);
+                    }
+                }
+            }
+        }
+
+        // The non-finite samples only reach the output channels using their input channels,
+        // like the execution plan skipping the zero coefficients.
+        let matrix = vec![
+            vec![1.0, 0.0, 0.5],
+            vec![0.0, 0.5, 0.0],
+            vec![0.0, -0.0, 0.25],
+        ];
+        let frames = 16;
+        let input: Vec<f32> = (0..frames * 3)
+            .map(|k| match k % 6 {
+                1 => f32::NAN,
+                4 => f32::INFINITY,
+                _ => k as f32 / 64.0,
+            })
+            .collect();
+        for level in levels() {
+            let kernel = F32Kernel::with_level(&matrix, level);
+            let mut output = vec![0.0; frames * 3];
+            kernel.mix(&input, &mut output);
+            for (input, output) in input.chunks(3).zip(output.chunks(3)) {
+                assert_eq!(output[0], input[0] + 0.5 * input[2], "{:?}", level);
+                assert_eq!(output[2], 0.25 * input[2], "{:?}", level);
+                if input[1].is_nan() {
+                    assert!(output[1].is_nan(), "{:?}", level);
+                } else {
+                    assert_eq!(output[1], 0.5 * input[1], "{:?}", level);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_i16_kernels() {
+        // The matrices with the coefficients fitting in 16 bits or not, with or without clipping.
+        let create_matrices = |m: usize, n: usize| -> Vec<Vec<Vec<f64>>> {
+            let small = (0..n)
+                .map(|i| (0..m).map(|j| ((i + j) % 3) as f64 * 0.1 - 0.1).collect())
+                .collect();
+            let mixed = (0..n)
+                .map(|i| {
+                    (0..m)
+                        .map(|j| [1.0, -0.7, 0.0, 0.45][(i + j) % 4])
+                        .collect()
+                })
+                .collect();
+            let largest = (0..n)
+                .map(|i| {
+                    (0..m)
+                        .map(|j| if j == i % m { -1.99 } else { 0.0 })
+                        .collect()
+                })
+                .collect();
+            vec![small, mixed, largest]
+        };
+        for &m in [1, 2, 3, 5, 6, 8, 12].iter() {
+            for &n in [1, 2, 3, 6, 8].iter() {
+                for matrix in create_matrices(m, n) {
+                    // Limit the absolute row sums like the mixer does.
+                    let matrix: Vec<Vec<f64>> = matrix
+                        .iter()
+                        .map(|row: &Vec<f64>| {
+                            let sum: f64 = row.iter().map(|coef| coef.abs()).sum();
+                            let scale = if sum > 1.99 { 1.99 / sum } else { 1.0 };
+                            row.iter().map(|coef| coef * scale).collect()
+                        })
+                        .collect();
+                    let would_overflow = i16::would_overflow_from_coefficient_value(&matrix)
+                        .expect("would_overflow must have value for i16");
+
+                    // Every i16 value appears once in the input, scattered over the channels.
+                    let frames = (1 << 16) / m + 1;
+                    let input: Vec<i16> = (0..frames * m)
+                        .map(|k| (k as u16).wrapping_mul(40503) as i16)
+                        .collect();
+                    let mut expected = vec![0; frames * n];
+                    for f in 0..frames {
+                        for i in 0..n {
+                            let mut value = 0;
+                            for j in 0..m {
+                                value += i16::coefficient_from_f64(matrix[i][j])
+                                    * i32::from(input[f * m + j]);
+                            }
+                            expected[f * n + i] =
+                                i16::from_coefficient_value(value, Some(would_overflow));
+                        }
+                    }
+
+                    for level in levels() {
+                        let kernel = I16Kernel::with_level(&matrix, would_overflow, level);
+                        let mut output = vec![0; frames * n];
+                        kernel.mix(&input, &mut output);
+                        assert_eq!(output, expected, "{:?} {}x{}", level, n, m);
+                        // The frame counts leaving remaining samples.
+                        for &frames in [0, 1, 3, 17].iter() {
+                            let mut output = vec![0; frames * n];
+                            kernel.mix(&input[..frames * m], &mut output);
+                            assert_eq!(output, expected[..frames * n], "{:?} {}x{}", level, n, m);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_create_kernel() {
+        let matrix = vec![vec![0.5, 0.5]];
+        assert!(matches!(
+            Kernel::create::<f32, f32>(&matrix, None),
+            Some(Kernel::F32(_))
+        ));
+        assert!(matches!(
+            Kernel::create::<i16, i16>(&matrix, Some(false)),
+            Some(Kernel::I16(_))
+        ));
+        assert!(Kernel::create::<i16, f32>(&matrix, None).is_none());
+        assert!(Kernel::create::<f64, f64>(&matrix, None).is_none());
+    }
+
+    #[test]
+    fn test_lcm() {
+        assert_eq!(lcm(2, 8), 8);
+        assert_eq!(lcm(6, 8), 24);
+        assert_eq!(lcm(1, 4), 4);
+        assert_eq!(lcm(12, 4), 12);
+    }
+}
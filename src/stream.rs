@@ -0,0 +1,164 @@
+use crate::channel::Channel;
+use crate::coefficient::{Coefficient, MixerError};
+use crate::config::MixerConfig;
+use crate::dither::Ditherer;
+use crate::protection::Protector;
+use crate::sample::Sample;
+use crate::Mixer;
+
+// A mixer for one continuous stream, which owns the state of the processing depending on the
+// previous samples of the stream: the dither and noise shaping applied when the mixed data is
+// written to integer samples, and the output protection applied when it's written to float
+// samples. The mixing is the same as the one of Mixer otherwise, but it needs mutable access, so
+// each stream must have its own MixerStream.
+#[derive(Debug)]
+pub struct MixerStream<I, O = I>
+where
+    I: Sample,
+    O: Sample,
+{
+    mixer: Mixer<I, O>,
+    // None if the config enables no stateful processing for the output samples.
+    state: Option<State>,
+}
+
+impl<I, O> MixerStream<I, O>
+where
+    I: Sample,
+    O: Sample,
+{
+    // Create a stream mixer whose mixing matrix is generated with the levels in the given config,
+    // and which applies the dither, noise shaping and output protection in the config. Return an
+    // error if there are duplicate non-silence channels in input or output channels, or the
+    // output protection is invalid.
+    pub fn with_config(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let coefficient = Coefficient::create(input_channels, output_channels, config)?;
+        Self::with_coefficient(coefficient, config)
+    }
+
+    // Create a stream mixer with the given NxM mixing matrix, which applies the dither, noise
+    // shaping and output protection in the given config. The levels and normalization in the
+    // config are not used. See Mixer::from_matrix for the matrix.
+    pub fn from_matrix(
+        input_channels: &[Channel],
+        output_channels: &[Channel],
+        matrix: &[Vec<f64>],
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let coefficient = Coefficient::create_with_matrix(input_channels, output_channels, matrix)?;
+        Self::with_coefficient(coefficient, config)
+    }
+
+    fn with_coefficient(
+        coefficient: Coefficient<O::Native>,
+        config: &MixerConfig,
+    ) -> Result<Self, MixerError> {
+        let state = State::new(
+            config,
+            coefficient.output_channels().len(),
+            coefficient.would_overflow_from_coefficient_value(),
+        )?;
+        Ok(Self {
+            mixer: Mixer::with_coefficient(coefficient),
+            state,
+        })
+    }
+
+    // Mix one frame. See Mixer::mix.
+    pub fn mix(&mut self, input_buffer: &[I], output_buffer: &mut [O]) {
+        self.mixer
+            .mix_with_state(self.state.as_mut(), input_buffer, output_buffer);
+    }
+
+    // Mix the interleaved frames and return the number of mixed frames. See
+    // Mixer::mix_interleaved.
+    pub fn mix_interleaved(&mut self, input_buffer: &[I], output_buffer: &mut [O]) -> usize {
+        self.mixer
+            .mix_interleaved_with_state(self.state.as_mut(), input_buffer, output_buffer)
+    }
+
+    // Mix the planar frames and return the number of mixed frames. See Mixer::mix_planar.
+    pub fn mix_planar(&mut self, input_buffers: &[&[I]], output_buffers: &mut [&mut [O]]) -> usize {
+        self.mixer
+            .mix_planar_with_state(self.state.as_mut(), input_buffers, output_buffers)
+    }
+
+    // Mix the interleaved frames to the planar buffers and return the number of mixed frames.
+    // See Mixer::mix_interleaved_to_planar.
+    pub fn mix_interleaved_to_planar(
+        &mut self,
+        input_buffer: &[I],
+        output_buffers: &mut [&mut [O]],
+    ) -> usize {
+        self.mixer.mix_interleaved_to_planar_with_state(
+            self.state.as_mut(),
+            input_buffer,
+            output_buffers,
+        )
+    }
+
+    // Mix the planar frames to the interleaved buffer and return the number of mixed frames.
+    // See Mixer::mix_planar_to_interleaved.
+    pub fn mix_planar_to_interleaved(
+        &mut self,
+        input_buffers: &[&[I]],
+        output_buffer: &mut [O],
+    ) -> usize {
+        self.mixer.mix_planar_to_interleaved_with_state(
+            self.state.as_mut(),
+            input_buffers,
+            output_buffer,
+        )
+    }
+
+    // Return the number of frames the output data is delayed by, which is the lookahead of the
+    // limiter if it's used, or 0 otherwise. The mixed data of the last frames stay in the
+    // limiter until the following frames are mixed.
+    pub fn latency(&self) -> usize {
+        match &self.state {
+            Some(State::Protection(protector)) => protector.latency(),
+            _ => 0,
+        }
+    }
+
+    // Return the mixer of the stream, which has the channels and the mixing matrix.
+    pub fn mixer(&self) -> &Mixer<I, O> {
+        &self.mixer
+    }
+}
+
+// The state of the processing depending on the previous samples of a stream.
+#[derive(Debug)]
+pub enum State {
+    // The dither and noise shaping state for the integer output samples.
+    Dither(Ditherer),
+    // The output protection state for the float output samples.
+    Protection(Protector),
+}
+
+impl State {
+    // Return the state for the output samples with the given channels, whose would_overflow is
+    // Some for the integer types, or None if the config enables no stateful processing for them.
+    // The float output samples are not quantized, so they are never dithered, and the integer
+    // output samples are always clipped, so they need no protection.
+    pub fn new(
+        config: &MixerConfig,
+        channels: usize,
+        would_overflow: Option<bool>,
+    ) -> Result<Option<Self>, MixerError> {
+        Ok(match would_overflow {
+            Some(_) => Ditherer::new(
+                config.dither(),
+                config.noise_shaping(),
+                config.dither_seed(),
+                channels,
+            )
+            .map(State::Dither),
+            None => Protector::new(config.output_protection(), channels)?.map(State::Protection),
+        })
+    }
+}